@@ -2,7 +2,6 @@ use agora_allosaurus_rs::accumulator::{
     Accumulator, Coefficient, Element, MembershipWitness, PublicKey, SecretKey,
 };
 use blsful::inner_types::*;
-use rand::SeedableRng;
 use rayon::prelude::*;
 use serde::{Serialize, Serializer};
 use std::collections::{HashMap, HashSet};
@@ -11,7 +10,10 @@ use uint_zigzag::Uint;
 
 use criterion::{criterion_group, criterion_main, Criterion};
 
-use agora_allosaurus_rs::{AccParams, PublicKeys, Server, User, UserID, UserUpdate};
+use agora_allosaurus_rs::{
+    AccParams, EpochHead, EpochSecretKey, PublicKeys, RangeUpdates, Server, ServerUpdate,
+    TransparencyLog, User, UserID, UserUpdate, CREDENTIAL_LIFETIME,
+};
 
 //-------BENCHMARK PARAMETERS ------//
 
@@ -56,7 +58,7 @@ fn single_server(c: &mut Criterion) {
         let mut acc = Accumulator::with_elements(&key, items.as_slice());
 
         // Creats a witness for some user
-        let y = items.last().unwrap().clone();
+        let y = *items.last().unwrap();
         let witness = MembershipWitness::new(y, acc, &key).unwrap();
 
         // Gets set of deletions
@@ -69,7 +71,7 @@ fn single_server(c: &mut Criterion) {
         // Benchmark of deletion method
         c.bench_function("Single-server server-side update", |b| {
             b.iter(|| {
-                let mut new_acc = acc.clone();
+                let mut new_acc = acc;
                 let mut deltas = vec![Vec::new(); split_deletions.len()];
                 for (i, deletion) in split_deletions.iter().enumerate() {
                     deltas[i] = new_acc.update_assign(&key, &[], deletion);
@@ -102,9 +104,9 @@ fn single_server(c: &mut Criterion) {
         // User repeatedly processes these deletions
         c.bench_function("Single-server user-side update", |b| {
             b.iter(|| {
-                let mut new_witness = witness.clone();
+                let mut new_witness = witness;
                 for (i, deletion) in split_deletions.iter().enumerate() {
-                    new_witness.batch_update_assign(y, &[], &deletion, &deltas[i]);
+                    new_witness.batch_update_assign(y, &[], deletion, &deltas[i]);
                 }
             })
         });
@@ -129,7 +131,7 @@ fn batch_update(c: &mut Criterion) {
         let mut acc = Accumulator::with_elements(&key, items.as_slice());
 
         // Takes the last user, gives them a witness
-        let y = items.last().unwrap().clone();
+        let y = *items.last().unwrap();
         let witness = MembershipWitness::new(y, acc, &key).unwrap();
 
         // Creates lists of elements to add and delete
@@ -211,14 +213,30 @@ fn allosaur_update(c: &mut Criterion) {
         // this is necessary for our fast and lazy delete to run the benchmark
         // but does not reflect how servers would actually handle secret keys
         let mut servers: Vec<Server> = (0..SHARES)
-            .map(|_| Server {
-                accumulators: vec![accumulator],
-                witness_secret_key: alpha.clone(),
-                public_keys,
-                sign_secret_key: s.clone(),
-                all_users: all_users.clone(),
-                all_witnesses: all_witnesses.clone(),
-                deletions: Vec::new(),
+            .map(|_| {
+                let epoch_secret_key = EpochSecretKey::new();
+                let head = EpochHead::new(1, accumulator, None)
+                    .sign(&epoch_secret_key)
+                    .unwrap();
+                let mut log = TransparencyLog::default();
+                log.append(&head.head).unwrap();
+                Server {
+                    accumulators: vec![accumulator],
+                    witness_secret_key: alpha,
+                    public_keys,
+                    sign_secret_key: s,
+                    all_users: all_users.clone(),
+                    all_witnesses: all_witnesses.clone(),
+                    deletions: Vec::new(),
+                    range_updates: RangeUpdates::default(),
+                    epoch_secret_key,
+                    encryption_secret_key: SecretKey::new(None),
+                    heads: vec![head],
+                    epoch_key_share: None,
+                    certified: Vec::new(),
+                    log,
+                    credential_lifetime: CREDENTIAL_LIFETIME,
+                }
             })
             .collect();
 
@@ -260,18 +278,18 @@ fn allosaur_update(c: &mut Criterion) {
 
         // Benchmark the server side, for only one server
         c.bench_function("ALLOSAUR server-side update ", |b| {
-            b.iter(|| servers[0].update(user_d, &user_shares[0]))
+            b.iter(|| servers[0].update(servers[0].get_epoch(), user_d, &user_shares[0]))
         });
 
         // Actually get the server responses, from all servers
-        let dvs: Vec<(Vec<Scalar>, Vec<G1Projective>)> = (0..SHARES)
-            .map(|i| servers[i].update(user_d, &user_shares[i]))
+        let dvs: Vec<ServerUpdate> = (0..SHARES)
+            .map(|i| servers[i].update(servers[i].get_epoch(), user_d, &user_shares[i]))
             .collect();
 
         // Get the length of data sent back to the user
         let server_user_message = ServerUpdateMessage {
-            d_poly: dvs[0].0.clone(),
-            v_poly: dvs[0].1.clone(),
+            d_poly: dvs[0].ds.clone(),
+            v_poly: dvs[0].vs.clone(),
         };
         // Print the length of data sent from *all* servers
         println!(
@@ -180,7 +180,7 @@ mod tests {
     #[test]
     fn coefficient_test() {
         let key = SecretKey::new(Some(b"1234567890"));
-        let data = vec![
+        let data = [
             Element::hash(b"1"),
             Element::hash(b"2"),
             Element::hash(b"3"),
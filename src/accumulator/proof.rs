@@ -849,14 +849,14 @@ mod tests {
 
     #[test]
     fn basic_nonmembership_proof() {
-        let mut rng = rand::rngs::OsRng;
+        let rng = rand::rngs::OsRng;
         let sk = SecretKey::new(None);
         let pk = PublicKey::from(&sk);
         let proof_params = ProofParams::new(pk, None);
         let blinding_factor = Some(Element(generate_fr(
             SALT,
             Some(b"basic_nonmembership_proof_blinding_factor"),
-            &mut rng,
+            rng,
         )));
         let elements = [
             Element::hash(b"1"),
@@ -891,7 +891,7 @@ mod tests {
     fn growing_accumulator() {
         use core::convert::TryFrom;
 
-        let mut rng = rand::rngs::OsRng;
+        let rng = rand::rngs::OsRng;
         let sk = SecretKey::try_from(&[
             83, 88, 211, 208, 98, 73, 80, 160, 247, 119, 30, 138, 197, 40, 149, 84, 224, 194, 132,
             99, 42, 220, 247, 225, 118, 194, 100, 61, 247, 72, 186, 15,
@@ -923,7 +923,7 @@ mod tests {
         let blinding_factor = Some(Element(generate_fr(
             SALT,
             Some(b"basic_nonmembership_proof_blinding_factor"),
-            &mut rng,
+            rng,
         )));
 
         let elements = [
@@ -60,18 +60,11 @@ pub const SALT: &[u8] = b"VB-ACC-HASH-SALT-";
 pub struct PolynomialG1(pub Vec<G1Projective>);
 
 impl PolynomialG1 {
-    #[cfg(any(feature = "std", feature = "alloc"))]
     /// Initialize this polynomial with the expected capacity
     pub fn with_capacity(size: usize) -> Self {
         Self(Vec::with_capacity(size))
     }
 
-    #[cfg(not(any(feature = "std", feature = "alloc")))]
-    /// Initialize this polynomial with the expected capacity
-    pub fn with_capacity(_size: usize) -> Self {
-        Self(Vec::new())
-    }
-
     /// Return the result of evaluating the polynomial with the specified point
     pub fn evaluate(&self, x: Scalar) -> Option<G1Projective> {
         if self.0.is_empty() {
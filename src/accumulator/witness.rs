@@ -576,7 +576,7 @@ mod tests {
 
         assert!(wit.verify(y, pubkey, acc));
 
-        let data = vec![
+        let data = [
             Element::hash(b"1"),
             Element::hash(b"2"),
             Element::hash(b"3"),
@@ -622,7 +622,7 @@ mod tests {
 
         assert!(wit.verify(y, pubkey, acc));
 
-        let data = vec![
+        let data = [
             Element::hash(b"1"),
             Element::hash(b"2"),
             Element::hash(b"3"),
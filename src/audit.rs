@@ -0,0 +1,191 @@
+use crate::accumulator::{Element, PublicKey};
+use crate::log::TransparencyLog;
+use crate::servers::Server;
+use crate::utils::{bisect_failures, AccParams, UserID};
+use blsful::inner_types::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// A problem `Server::audit` found in the server's state
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+pub enum Discrepancy {
+    /// The witness public key does not match the witness secret key
+    WitnessKeyMismatch,
+    /// The signing public key does not match the signing secret key
+    SignKeyMismatch,
+    /// The stored witness does not verify against the latest accumulator
+    InvalidWitness(UserID),
+    /// A witness is stored for an element that is not in `all_users`
+    WitnessWithoutUser(UserID),
+    /// An element in `all_users` has neither a witness nor a deletion
+    UserWithoutWitness(UserID),
+    /// A deleted element still has a witness
+    DeletedUserWithWitness(UserID),
+    /// The number of accumulators does not match the number of deletions
+    EpochCountMismatch {
+        /// The number of accumulators
+        accumulators: usize,
+        /// The number of deletions
+        deletions: usize,
+    },
+    /// The move to this epoch is not the deletion recorded for it
+    InvalidDeletion(usize),
+    /// The aggregated range updates do not cover every deletion
+    RangeUpdateMismatch,
+    /// The signed head of this epoch is missing, invalid,
+    /// or does not match the accumulator and the previous head
+    InvalidHead(usize),
+    /// The transparency log does not match the signed heads
+    LogMismatch,
+}
+
+impl Server {
+    /// Checks the server's whole state and returns every discrepancy found.
+    /// Witnesses and deletions are checked with batched pairings,
+    /// bisecting only when a batch fails
+    pub fn audit(&self, params: &AccParams) -> Vec<Discrepancy> {
+        let mut found = Vec::new();
+
+        // Keys
+        if self.public_keys.witness_key.0 != params.get_p2() * self.witness_secret_key.0 {
+            found.push(Discrepancy::WitnessKeyMismatch);
+        }
+        if self.public_keys.sign_key.0 != params.get_k2() * self.sign_secret_key.0 {
+            found.push(Discrepancy::SignKeyMismatch);
+        }
+
+        // Users and witnesses
+        let deleted: HashSet<UserID> = self.deletions.iter().copied().collect();
+        let mut users: Vec<&UserID> = self.all_users.iter().collect();
+        users.sort_unstable_by_key(|y| y.0.to_be_bytes());
+        for y in users {
+            if !self.all_witnesses.contains_key(y) && !deleted.contains(y) {
+                found.push(Discrepancy::UserWithoutWitness(*y));
+            }
+        }
+        let mut witnesses: Vec<(UserID, G1Projective)> =
+            self.all_witnesses.iter().map(|(y, w)| (*y, w.0)).collect();
+        witnesses.sort_unstable_by_key(|(y, _)| y.0.to_be_bytes());
+        for (y, _) in &witnesses {
+            if !self.all_users.contains(y) {
+                found.push(Discrepancy::WitnessWithoutUser(*y));
+            }
+            if deleted.contains(y) {
+                found.push(Discrepancy::DeletedUserWithWitness(*y));
+            }
+        }
+        let accumulator = self.get_accumulator();
+        let checks: Vec<PairingCheck> = witnesses
+            .iter()
+            .map(|(y, w)| PairingCheck {
+                y: *y,
+                witness: *w,
+                accumulator: accumulator.0,
+            })
+            .collect();
+        let witness_key = self.public_keys.witness_key;
+        for i in failing_checks(&checks, params, &witness_key) {
+            found.push(Discrepancy::InvalidWitness(checks[i].y));
+        }
+
+        // Epochs and deletions
+        if self.accumulators.len() != self.deletions.len() + 1 {
+            found.push(Discrepancy::EpochCountMismatch {
+                accumulators: self.accumulators.len(),
+                deletions: self.deletions.len(),
+            });
+        }
+        // V_{t+1} is a witness for y_t against V_t
+        let checks: Vec<PairingCheck> = self
+            .deletions
+            .iter()
+            .zip(self.accumulators.windows(2))
+            .map(|(y, v)| PairingCheck {
+                y: *y,
+                witness: v[1].0,
+                accumulator: v[0].0,
+            })
+            .collect();
+        for i in failing_checks(&checks, params, &witness_key) {
+            found.push(Discrepancy::InvalidDeletion(i + 2));
+        }
+        if self.range_updates.len() != self.deletions.len() {
+            found.push(Discrepancy::RangeUpdateMismatch);
+        }
+
+        // Heads and log
+        let epoch_key = self.get_epoch_public_key();
+        let mut log = TransparencyLog::default();
+        for (i, accumulator) in self.accumulators.iter().enumerate() {
+            let epoch = i + 1;
+            let valid = self.get_epoch_head(epoch).is_some_and(|h| {
+                h.head.epoch == epoch
+                    && h.head.accumulator == *accumulator
+                    && h.verify(&epoch_key).is_ok()
+                    && (i == 0 || h.head.follows(&self.heads[i - 1].head))
+            });
+            if !valid {
+                found.push(Discrepancy::InvalidHead(epoch));
+            }
+        }
+        for epoch in self.accumulators.len() + 1..=self.heads.len() {
+            found.push(Discrepancy::InvalidHead(epoch));
+        }
+        let log_matches = self.heads.len() == self.log.len()
+            && self.heads.iter().all(|h| log.append(&h.head).is_ok())
+            && log.checkpoint() == self.log.checkpoint();
+        if !log_matches {
+            found.push(Discrepancy::LogMismatch);
+        }
+        found
+    }
+}
+
+// e(witness, yP + Q) = e(accumulator, P)
+#[derive(Copy, Clone)]
+struct PairingCheck {
+    y: UserID,
+    witness: G1Projective,
+    accumulator: G1Projective,
+}
+
+// Returns the indices of the checks that fail
+fn failing_checks(
+    checks: &[PairingCheck],
+    params: &AccParams,
+    witness_key: &PublicKey,
+) -> Vec<usize> {
+    bisect_failures(checks, &|c: &[PairingCheck]| {
+        batch_check(c, params, witness_key)
+    })
+}
+
+// With random r_i, checks all at once as
+// e(sum r_i (y_i C_i - V_i), P) * e(sum r_i C_i, Q) = 1
+fn batch_check(checks: &[PairingCheck], params: &AccParams, witness_key: &PublicKey) -> bool {
+    let r: Vec<Scalar> = checks.iter().map(|_| Element::random().0).collect();
+    let mut points = Vec::with_capacity(2 * checks.len());
+    let mut scalars = Vec::with_capacity(2 * checks.len());
+    for (c, r) in checks.iter().zip(r.iter()) {
+        points.push(c.witness);
+        scalars.push(*r * c.y.0);
+        points.push(c.accumulator);
+        scalars.push(-*r);
+    }
+    let lhs = G1Projective::sum_of_products(&points, &scalars);
+    let witnesses: Vec<G1Projective> = checks.iter().map(|c| c.witness).collect();
+    let rhs = G1Projective::sum_of_products(&witnesses, &r);
+    multi_miller_loop(&[
+        (
+            &lhs.to_affine(),
+            &G2Prepared::from(params.get_p2().to_affine()),
+        ),
+        (
+            &rhs.to_affine(),
+            &G2Prepared::from(witness_key.0.to_affine()),
+        ),
+    ])
+    .final_exponentiation()
+    .is_identity()
+    .into()
+}
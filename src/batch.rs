@@ -0,0 +1,197 @@
+use crate::accumulator::Accumulator;
+use crate::utils::{bisect_failures, AccParams, PublicKeys, SECURITY_BYTES};
+use crate::witness::CommittedMembershipProof;
+use blsful::inner_types::*;
+use rand::RngCore;
+
+/// Checks many committed membership proofs made under the same
+/// parameters and public keys at once.
+/// The pairing checks of all proofs are combined with random weights
+/// into a single multi-Miller loop of four pairings
+#[derive(Clone, Debug)]
+pub struct MembershipProofBatch {
+    params: AccParams,
+    public_keys: PublicKeys,
+    entries: Vec<BatchEntry>,
+}
+
+#[derive(Copy, Clone, Debug)]
+struct BatchEntry {
+    proof: CommittedMembershipProof,
+    accumulator: Accumulator,
+    ephemeral_challenge: [u8; 2 * SECURITY_BYTES],
+}
+
+impl MembershipProofBatch {
+    /// Creates an empty batch for proofs under these parameters and keys
+    pub fn new(params: AccParams, public_keys: PublicKeys) -> Self {
+        Self {
+            params,
+            public_keys,
+            entries: Vec::new(),
+        }
+    }
+
+    /// Adds a proof with the accumulator and challenge it was made for
+    pub fn add(
+        &mut self,
+        proof: CommittedMembershipProof,
+        accumulator: Accumulator,
+        ephemeral_challenge: [u8; 2 * SECURITY_BYTES],
+    ) {
+        self.entries.push(BatchEntry {
+            proof,
+            accumulator,
+            ephemeral_challenge,
+        });
+    }
+
+    /// The number of proofs in the batch
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the batch has no proofs
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Checks every proof and returns the indices of the invalid ones,
+    /// in the order they were added. If the combined check fails,
+    /// the batch is bisected to find the invalid proofs
+    pub fn verify(&self) -> Vec<usize> {
+        // Challenges only need hashing, so check them one by one
+        let (valid, mut invalid): (Vec<usize>, Vec<usize>) =
+            (0..self.entries.len()).partition(|i| {
+                let e = &self.entries[*i];
+                e.proof.check_challenge(
+                    &self.params,
+                    &self.public_keys,
+                    &e.accumulator,
+                    &e.ephemeral_challenge,
+                )
+            });
+        let failing = bisect_failures(&valid, &|indices: &[usize]| {
+            let entries: Vec<&BatchEntry> = indices.iter().map(|i| &self.entries[*i]).collect();
+            self.check_commitments(&entries)
+        });
+        invalid.extend(failing.into_iter().map(|i| valid[i]));
+        invalid.sort_unstable();
+        invalid
+    }
+
+    // Checks the commitments of every entry against its responses:
+    // T_1 = s_1X + s_2Y + s_3Z - cR
+    // T_2 = s_4X + s_5Y + s_6Z - s_7R
+    // Pi_1 = e(s_0K_1 - s_7U_1 + s_4Z + cK_0, K) * e(s_1Z - cU_1, Q_m)
+    // Pi_2 = e(-s_7U_2 + s_5Z + cV, P) * e(s_2Z - cU_2, Q)
+    // Each equation is weighted by a random 128-bit scalar and summed,
+    // so the pairings collapse to one per G2 point
+    fn check_commitments(&self, entries: &[&BatchEntry]) -> bool {
+        let params = &self.params;
+        let n = entries.len();
+        let mut bases = [Scalar::ZERO; 3];
+        let mut g1_points = Vec::with_capacity(3 * n + 3);
+        let mut g1_scalars = Vec::with_capacity(3 * n + 3);
+
+        let mut k1 = Scalar::ZERO;
+        let mut k0 = Scalar::ZERO;
+        let mut z_1 = Scalar::ZERO;
+        let mut z_qm = Scalar::ZERO;
+        let mut z_2 = Scalar::ZERO;
+        let mut z_q = Scalar::ZERO;
+        let mut k_points = Vec::with_capacity(n);
+        let mut k_scalars = Vec::with_capacity(n);
+        let mut qm_points = Vec::with_capacity(n);
+        let mut qm_scalars = Vec::with_capacity(n);
+        let mut p_points = Vec::with_capacity(2 * n);
+        let mut p_scalars = Vec::with_capacity(2 * n);
+        let mut q_points = Vec::with_capacity(n);
+        let mut q_scalars = Vec::with_capacity(n);
+        let mut pi = Gt::IDENTITY;
+
+        for e in entries {
+            let p = &e.proof.proof;
+            let [a, b, c, d] = [
+                random_weight(),
+                random_weight(),
+                random_weight(),
+                random_weight(),
+            ];
+
+            // G1 equations
+            bases[0] += a * p.s_1 + b * p.s_4;
+            bases[1] += a * p.s_2 + b * p.s_5;
+            bases[2] += a * p.s_3 + b * p.s_6;
+            g1_points.extend_from_slice(&[p.r, e.proof.t_1, e.proof.t_2]);
+            g1_scalars.extend_from_slice(&[-(a * p.challenge + b * p.s_7), -a, -b]);
+
+            // Pi_1
+            k1 += c * p.s_0;
+            z_1 += c * p.s_4;
+            k0 += c * p.challenge;
+            k_points.push(p.u_1);
+            k_scalars.push(-(c * p.s_7));
+            z_qm += c * p.s_1;
+            qm_points.push(p.u_1);
+            qm_scalars.push(-(c * p.challenge));
+
+            // Pi_2
+            z_2 += d * p.s_5;
+            p_points.extend_from_slice(&[p.u_2, e.accumulator.0]);
+            p_scalars.extend_from_slice(&[-(d * p.s_7), d * p.challenge]);
+            z_q += d * p.s_2;
+            q_points.push(p.u_2);
+            q_scalars.push(-(d * p.challenge));
+
+            pi += e.proof.pi_1 * c;
+            pi += e.proof.pi_2 * d;
+        }
+
+        g1_points.extend_from_slice(&[params.get_x1(), params.get_y1(), params.get_z1()]);
+        g1_scalars.extend_from_slice(&bases);
+        if !bool::from(G1Projective::sum_of_products(&g1_points, &g1_scalars).is_identity()) {
+            return false;
+        }
+
+        k_points.extend_from_slice(&[params.get_k1(), params.get_z1(), params.get_k0()]);
+        k_scalars.extend_from_slice(&[k1, z_1, k0]);
+        qm_points.push(params.get_z1());
+        qm_scalars.push(z_qm);
+        p_points.push(params.get_z1());
+        p_scalars.push(z_2);
+        q_points.push(params.get_z1());
+        q_scalars.push(z_q);
+
+        let k = G1Projective::sum_of_products(&k_points, &k_scalars);
+        let qm = G1Projective::sum_of_products(&qm_points, &qm_scalars);
+        let p = G1Projective::sum_of_products(&p_points, &p_scalars);
+        let q = G1Projective::sum_of_products(&q_points, &q_scalars);
+        let pairings = multi_miller_loop(&[
+            (
+                &k.to_affine(),
+                &G2Prepared::from(params.get_k2().to_affine()),
+            ),
+            (
+                &qm.to_affine(),
+                &G2Prepared::from(self.public_keys.sign_key.0.to_affine()),
+            ),
+            (
+                &p.to_affine(),
+                &G2Prepared::from(params.get_p2().to_affine()),
+            ),
+            (
+                &q.to_affine(),
+                &G2Prepared::from(self.public_keys.witness_key.0.to_affine()),
+            ),
+        ])
+        .final_exponentiation();
+        pairings == pi
+    }
+}
+
+fn random_weight() -> Scalar {
+    Scalar::from(
+        u128::from(rand::rngs::OsRng.next_u64()) << 64 | u128::from(rand::rngs::OsRng.next_u64()),
+    )
+}
@@ -0,0 +1,341 @@
+use crate::accumulator::{generate_fr, schnorr, Accumulator, Element, SALT};
+use crate::utils::{AccParams, PublicKeys, UserID, SECURITY_BYTES};
+use crate::witness::{MembershipProof, Statement, Witness};
+use blsful::inner_types::*;
+use merlin::Transcript;
+use serde::{Deserialize, Serialize};
+
+/// A BBS+ signature over the user's ID y, secret key x, expiry epoch t and attributes m_1,..m_n,
+/// A = (K0 + xK + sH_0 + yH_y + tH_e + sum m_iH_i)/(q_m + e), issued by `Server::credential`
+/// or with the witness by `Server::witness`.
+/// Together with the user's `Witness` it is a full anonymous credential:
+/// presentations prove membership and reveal only the chosen attributes
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Credential {
+    /// The signed attributes, e.g. role and tier
+    pub attributes: Vec<Element>,
+    /// The first epoch the credential is no longer valid at, decided by the server
+    pub expiry: u64,
+    /// The signature A over y, x and the attributes
+    pub signature: G1Projective,
+    /// The signature's exponent e
+    pub e: Scalar,
+    /// The signature's blinding s
+    pub s: Scalar,
+}
+
+impl Credential {
+    /// Verifies the credential for the user's ID and the secret key in their witness
+    pub fn verify(
+        &self,
+        witness: &Witness,
+        public_keys: &PublicKeys,
+        params: &AccParams,
+        y: &UserID,
+    ) -> Result<(), &'static str> {
+        // e(A, Q_m + eK) = e(B, K)
+        let check = multi_miller_loop(&[
+            (
+                &self.signature.to_affine(),
+                &G2Prepared::from((params.get_k2() * self.e + public_keys.sign_key.0).to_affine()),
+            ),
+            (
+                &(-self.message(params, &witness.secret_key.0, y)).to_affine(),
+                &G2Prepared::from(params.get_k2().to_affine()),
+            ),
+        ])
+        .final_exponentiation()
+        .is_identity();
+        if check.into() {
+            Ok(())
+        } else {
+            Err("failed")
+        }
+    }
+
+    // B = K0 + xK + sH_0 + yH_y + tH_e + sum m_iH_i
+    fn message(&self, params: &AccParams, x: &Scalar, y: &UserID) -> G1Projective {
+        params.get_k0()
+            + params.get_k1() * x
+            + params.get_h0() * self.s
+            + params.get_hy() * y.0
+            + params.get_he() * Scalar::from(self.expiry)
+            + attribute_message(params, &self.attributes)
+    }
+}
+
+/// A membership proof for a `Credential` that reveals some of its attributes.
+/// The signature is randomized as A' = r_1A, Abar = r_1B - eA' and d = r_1B - r_2H_0,
+/// and the proof shows e(A', Q_m) = e(Abar, K) together with
+/// Abar - d = -eA' + r_2H_0 and
+/// K0 + sum m_iH_i over the disclosed attributes = r_3d - s'H_0 - xK - yH_y - tH_e - sum m_iH_i
+/// over the hidden ones, where r_3 = 1/r_1 and s' = s - r_2r_3.
+/// x and y are answered by s_0 and s_7 of the membership proof,
+/// the expiry t, which is never disclosed, by s_t = k_t + ct,
+/// and each hidden attribute by a response s_i = k_i + cm_i
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct AttributePresentation {
+    pub(crate) proof: MembershipProof,
+    pub(crate) num_attributes: usize,
+    pub(crate) disclosed: Vec<(usize, Element)>,
+    pub(crate) a_prime: G1Projective,
+    pub(crate) a_bar: G1Projective,
+    pub(crate) d: G1Projective,
+    pub(crate) s_e: Scalar,
+    pub(crate) s_r2: Scalar,
+    pub(crate) s_r3: Scalar,
+    pub(crate) s_s: Scalar,
+    pub(crate) s_t: Scalar,
+    pub(crate) responses: Vec<Scalar>,
+}
+
+impl AttributePresentation {
+    /// The underlying membership proof
+    pub fn proof(&self) -> MembershipProof {
+        self.proof
+    }
+
+    /// The number of attributes in the credential
+    pub fn num_attributes(&self) -> usize {
+        self.num_attributes
+    }
+
+    /// The disclosed attributes with their indices, in increasing order of index
+    pub fn disclosed(&self) -> &[(usize, Element)] {
+        &self.disclosed
+    }
+
+    /// Verifies the presentation against the verifier's ephemeral challenge.
+    /// The disclosed attributes are then signed in the prover's credential
+    pub fn verify(
+        &self,
+        params: &AccParams,
+        public_keys: &PublicKeys,
+        accumulator: &Accumulator,
+        ephemeral_challenge: &[u8; 2 * SECURITY_BYTES],
+    ) -> bool {
+        self.check_bound(params, public_keys, accumulator, |transcript| {
+            transcript.append_message(b"Ephemeral challenge", ephemeral_challenge)
+        })
+    }
+
+    // Checks a presentation made by `present` with the same `bind`
+    pub(crate) fn check_bound(
+        &self,
+        params: &AccParams,
+        public_keys: &PublicKeys,
+        accumulator: &Accumulator,
+        bind: impl FnOnce(&mut Transcript),
+    ) -> bool {
+        let in_order = self.disclosed.windows(2).all(|w| w[0].0 < w[1].0);
+        let in_range = self
+            .disclosed
+            .last()
+            .is_none_or(|(i, _)| *i < self.num_attributes);
+        if !in_order
+            || !in_range
+            || self.disclosed.len() + self.responses.len() != self.num_attributes
+            || bool::from(self.a_prime.is_identity())
+        {
+            return false;
+        }
+        // e(A', Q_m) = e(Abar, K)
+        let signed = multi_miller_loop(&[
+            (
+                &self.a_prime.to_affine(),
+                &G2Prepared::from(public_keys.sign_key.0.to_affine()),
+            ),
+            (
+                &(-self.a_bar).to_affine(),
+                &G2Prepared::from(params.get_k2().to_affine()),
+            ),
+        ])
+        .final_exponentiation()
+        .is_identity();
+        if !bool::from(signed) {
+            return false;
+        }
+        let c = self.proof.challenge;
+        // T_1 = -s_eA' + s_r2H_0 - c(Abar - d)
+        let t_1 = params.get_h0() * self.s_r2 - self.a_prime * self.s_e - (self.a_bar - self.d) * c;
+        // T_2 = s_r3d - s_s'H_0 - s_0K - s_7H_y - s_tH_e - sum s_iH_i over the hidden attributes
+        //     - c(K0 + sum m_iH_i over the disclosed attributes)
+        let mut disclosed = self.disclosed.iter().peekable();
+        let mut responses = self.responses.iter();
+        let attributes = (0..self.num_attributes).fold(G1Projective::IDENTITY, |sum, i| {
+            let scalar = match disclosed.next_if(|(j, _)| *j == i) {
+                Some((_, m)) => m.0 * c,
+                None => *responses.next().expect("one response per hidden attribute"),
+            };
+            sum + params.get_h1(i) * scalar
+        });
+        let t_2 = self.d * self.s_r3
+            - params.get_h0() * self.s_s
+            - params.get_k1() * self.proof.s_0
+            - params.get_hy() * self.proof.s_7
+            - params.get_he() * self.s_t
+            - attributes
+            - params.get_k0() * c;
+        Witness::check_bound(
+            &self.proof,
+            params,
+            public_keys,
+            accumulator,
+            |transcript| {
+                bind_signature(
+                    transcript,
+                    self.num_attributes,
+                    &self.disclosed,
+                    &[self.a_prime, self.a_bar, self.d],
+                    &[t_1, t_2],
+                );
+                bind(transcript)
+            },
+        )
+    }
+}
+
+impl Witness {
+    /// Constructs a membership proof for `credential` that reveals
+    /// the attributes at the `disclose` indices and hides the rest
+    pub fn make_attribute_presentation(
+        witness: &Witness,
+        user_id: &UserID,
+        statement: &Statement,
+        params: &AccParams,
+        credential: &Credential,
+        disclose: &[usize],
+        ephemeral_challenge: &[u8; 2 * SECURITY_BYTES],
+    ) -> Option<AttributePresentation> {
+        Self::present(
+            witness,
+            user_id,
+            statement,
+            params,
+            credential,
+            disclose,
+            |transcript, _| transcript.append_message(b"Ephemeral challenge", ephemeral_challenge),
+        )
+    }
+
+    // Makes an attribute presentation whose challenge also hashes whatever `bind` adds
+    // to the transcript. `bind` also gets the blinding k_t of the expiry,
+    // so that it can prove more about it; the response is then the presentation's s_t
+    pub(crate) fn present(
+        witness: &Witness,
+        user_id: &UserID,
+        statement: &Statement,
+        params: &AccParams,
+        credential: &Credential,
+        disclose: &[usize],
+        bind: impl FnOnce(&mut Transcript, Scalar),
+    ) -> Option<AttributePresentation> {
+        let num_attributes = credential.attributes.len();
+        if disclose.iter().any(|i| *i >= num_attributes) {
+            return None;
+        }
+        credential
+            .verify(witness, statement.public_keys, params, user_id)
+            .ok()?;
+        let (disclosed, hidden): (Vec<usize>, Vec<usize>) =
+            (0..num_attributes).partition(|i| disclose.contains(i));
+        let disclosed: Vec<(usize, Element)> = disclosed
+            .into_iter()
+            .map(|i| (i, credential.attributes[i]))
+            .collect();
+        let rng = rand::rngs::OsRng;
+        let blindings: Vec<Scalar> = (0..num_attributes)
+            .map(|i| {
+                if hidden.contains(&i) {
+                    generate_fr(SALT, None, rng)
+                } else {
+                    Scalar::ZERO
+                }
+            })
+            .collect();
+
+        // Randomize the signature: A' = r_1A, Abar = r_1B - eA', d = r_1B - r_2H_0
+        let r_1 = generate_fr(SALT, None, rng);
+        let r_2 = generate_fr(SALT, None, rng);
+        let r_3 = Option::<Scalar>::from(r_1.invert())?;
+        let s_prime = credential.s - r_2 * r_3;
+        let b = credential.message(params, &witness.secret_key.0, user_id);
+        let a_prime = credential.signature * r_1;
+        let a_bar = b * r_1 - a_prime * credential.e;
+        let d = b * r_1 - params.get_h0() * r_2;
+        let [k_e, k_r2, k_r3, k_s, k_t] = [(); 5].map(|_| generate_fr(SALT, None, rng));
+
+        let proof = Self::prove_bound(witness, user_id, statement, params, |transcript, k| {
+            // T_1 = -k_eA' + k_r2H_0
+            let t_1 = params.get_h0() * k_r2 - a_prime * k_e;
+            // T_2 = k_r3d - k_s'H_0 - k_0K - k_7H_y - k_tH_e - sum k_iH_i over the hidden attributes
+            let t_2 = hidden.iter().fold(
+                d * k_r3
+                    - params.get_h0() * k_s
+                    - params.get_k1() * k[0]
+                    - params.get_hy() * k[7]
+                    - params.get_he() * k_t,
+                |sum, i| sum - params.get_h1(*i) * blindings[*i],
+            );
+            bind_signature(
+                transcript,
+                num_attributes,
+                &disclosed,
+                &[a_prime, a_bar, d],
+                &[t_1, t_2],
+            );
+            bind(transcript, k_t)
+        })?;
+        let c = proof.challenge;
+        let responses = hidden
+            .iter()
+            .map(|i| schnorr(blindings[*i], credential.attributes[*i].0, c))
+            .collect();
+        Some(AttributePresentation {
+            proof,
+            num_attributes,
+            disclosed,
+            a_prime,
+            a_bar,
+            d,
+            s_e: schnorr(k_e, credential.e, c),
+            s_r2: schnorr(k_r2, r_2, c),
+            s_r3: schnorr(k_r3, r_3, c),
+            s_s: schnorr(k_s, s_prime, c),
+            s_t: schnorr(k_t, Scalar::from(credential.expiry), c),
+            responses,
+        })
+    }
+}
+
+// M = sum m_iH_i
+pub(crate) fn attribute_message(params: &AccParams, attributes: &[Element]) -> G1Projective {
+    attributes
+        .iter()
+        .enumerate()
+        .fold(G1Projective::IDENTITY, |sum, (i, m)| {
+            sum + params.get_h1(i) * m.0
+        })
+}
+
+// Adds the shape of the credential, the disclosed attributes,
+// the randomized signature and its commitments to the proof transcript
+fn bind_signature(
+    transcript: &mut Transcript,
+    num_attributes: usize,
+    disclosed: &[(usize, Element)],
+    signature: &[G1Projective; 3],
+    commitments: &[G1Projective; 2],
+) {
+    transcript.append_message(b"Attributes", &(num_attributes as u64).to_be_bytes());
+    for (i, m) in disclosed {
+        transcript.append_message(b"Disclosed index", &(*i as u64).to_be_bytes());
+        transcript.append_message(b"Disclosed attribute", &m.0.to_be_bytes());
+    }
+    for (label, point) in [b"A'".as_slice(), b"Abar", b"d", b"T_1", b"T_2"]
+        .into_iter()
+        .zip(signature.iter().chain(commitments))
+    {
+        transcript.append_message(label, &point.to_compressed());
+    }
+}
@@ -0,0 +1,146 @@
+use crate::accumulator::{generate_fr, schnorr, Accumulator, SALT};
+use crate::utils::{AccParams, PublicKeys, UserID, SECURITY_BYTES};
+use crate::witness::{MembershipProof, Statement, Witness};
+use blsful::inner_types::*;
+use merlin::Transcript;
+use serde::{Deserialize, Serialize};
+
+/// The secret key of a designated verifier
+#[derive(Clone, Deserialize, Serialize)]
+pub struct DesignatedVerifierKey(pub Scalar);
+
+/// The public key users make designated-verifier proofs for, V = vG
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+pub struct DesignatedVerifierPublicKey(pub G1Projective);
+
+impl DesignatedVerifierKey {
+    /// Generates a random verifier key
+    pub fn random() -> Self {
+        Self(generate_fr(SALT, None, rand::rngs::OsRng))
+    }
+
+    /// The matching public key
+    pub fn public_key(&self) -> DesignatedVerifierPublicKey {
+        DesignatedVerifierPublicKey(G1Projective::GENERATOR * self.0)
+    }
+
+    /// Makes a proof that verifies like a user's but proves nothing about membership.
+    /// Since the verifier can make these itself, a proof it receives
+    /// convinces nobody else that a user presented
+    pub fn simulate(
+        &self,
+        statement: &Statement,
+        params: &AccParams,
+        ephemeral_challenge: &[u8; 2 * SECURITY_BYTES],
+    ) -> DesignatedProof {
+        let rng = rand::rngs::OsRng;
+        let s = [(); 12].map(|_| generate_fr(SALT, None, rng));
+        let proof = MembershipProof {
+            epoch: statement.epoch,
+            u_1: G1Projective::GENERATOR * s[0],
+            u_2: G1Projective::GENERATOR * s[1],
+            r: G1Projective::GENERATOR * s[2],
+            challenge: s[3],
+            s_0: s[4],
+            s_1: s[5],
+            s_2: s[6],
+            s_3: s[7],
+            s_4: s[8],
+            s_5: s[9],
+            s_6: s[10],
+            s_7: s[11],
+        };
+        // Answer the trapdoor branch honestly with T_V = kG
+        let k = generate_fr(SALT, None, rng);
+        let public_key = self.public_key();
+        let t_v = G1Projective::GENERATOR * k;
+        let challenge = Witness::bound_challenge(
+            &proof,
+            params,
+            statement.public_keys,
+            statement.accumulator,
+            |transcript| bind(transcript, &public_key, &t_v, ephemeral_challenge),
+        );
+        let c_v = challenge - proof.challenge;
+        DesignatedProof {
+            proof,
+            c_v,
+            s_v: schnorr(k, self.0, c_v),
+        }
+    }
+}
+
+/// A membership proof that only convinces one designated verifier.
+/// It is an OR proof: either the prover is a member,
+/// or the prover knows the verifier's secret key.
+/// The membership branch answers challenge c - c_V,
+/// and the trapdoor branch T_V = s_VG - c_VV answers c_V
+#[derive(Copy, Clone, Debug, Deserialize, Serialize)]
+pub struct DesignatedProof {
+    pub(crate) proof: MembershipProof,
+    pub(crate) c_v: Scalar,
+    pub(crate) s_v: Scalar,
+}
+
+impl DesignatedProof {
+    /// The epoch the proof was made for
+    pub fn epoch(&self) -> usize {
+        self.proof.epoch
+    }
+
+    /// Verifies the proof as the verifier with `verifier_key`
+    pub fn verify(
+        &self,
+        params: &AccParams,
+        public_keys: &PublicKeys,
+        accumulator: &Accumulator,
+        verifier_key: &DesignatedVerifierPublicKey,
+        ephemeral_challenge: &[u8; 2 * SECURITY_BYTES],
+    ) -> bool {
+        // T_V = s_VG - c_VV
+        let t_v = G1Projective::GENERATOR * self.s_v - verifier_key.0 * self.c_v;
+        Witness::bound_challenge(
+            &self.proof,
+            params,
+            public_keys,
+            accumulator,
+            |transcript| bind(transcript, verifier_key, &t_v, ephemeral_challenge),
+        ) == self.proof.challenge + self.c_v
+    }
+}
+
+impl Witness {
+    /// Constructs a membership proof that only `verifier_key`'s verifier accepts,
+    /// by simulating knowledge of the verifier's secret key
+    pub fn make_designated_membership_proof(
+        witness: &Witness,
+        user_id: &UserID,
+        statement: &Statement,
+        params: &AccParams,
+        verifier_key: &DesignatedVerifierPublicKey,
+        ephemeral_challenge: &[u8; 2 * SECURITY_BYTES],
+    ) -> Option<DesignatedProof> {
+        // Simulate the trapdoor branch: T_V = s_VG - c_VV
+        let rng = rand::rngs::OsRng;
+        let c_v = generate_fr(SALT, None, rng);
+        let s_v = generate_fr(SALT, None, rng);
+        let t_v = G1Projective::GENERATOR * s_v - verifier_key.0 * c_v;
+        let proof = Self::prove_split(witness, user_id, statement, params, |transcript, _| {
+            bind(transcript, verifier_key, &t_v, ephemeral_challenge);
+            c_v
+        })?;
+        Some(DesignatedProof { proof, c_v, s_v })
+    }
+}
+
+// Adds the verifier key and the trapdoor commitment to the proof transcript
+fn bind(
+    transcript: &mut Transcript,
+    verifier_key: &DesignatedVerifierPublicKey,
+    t_v: &G1Projective,
+    ephemeral_challenge: &[u8; 2 * SECURITY_BYTES],
+) {
+    transcript.append_message(b"Designated verifier", &verifier_key.0.to_compressed());
+    transcript.append_message(b"T_V", &t_v.to_compressed());
+    transcript.append_message(b"Ephemeral challenge", ephemeral_challenge);
+}
@@ -0,0 +1,280 @@
+use crate::accumulator::Accumulator;
+use blsful::{Bls12381G2Impl, SignatureSchemes};
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Sha3_256};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Domain separation for signatures on epoch heads
+const EPOCH_HEAD_DST: &[u8] = b"ALLOSAUR_EPOCH_HEAD";
+
+/// The BLS secret key a server signs its epochs with
+pub type EpochSecretKey = blsful::SecretKey<Bls12381G2Impl>;
+/// The BLS public key to check a server's signed epochs
+pub type EpochPublicKey = blsful::PublicKey<Bls12381G2Impl>;
+/// A BLS signature on an epoch
+pub type EpochSignature = blsful::Signature<Bls12381G2Impl>;
+/// A server's share of the joint key that certifies epochs
+pub type EpochKeyShare = blsful::SecretKeyShare<Bls12381G2Impl>;
+/// The public key of a server's share of the joint key
+pub type EpochPublicKeyShare = blsful::PublicKeyShare<Bls12381G2Impl>;
+/// A server's partial signature on an epoch
+pub type EpochSignatureShare = blsful::SignatureShare<Bls12381G2Impl>;
+
+/// Splits a joint epoch key into shares for `num_servers` servers,
+/// any `threshold` of which can certify an epoch head
+pub fn split_epoch_key(
+    secret_key: &EpochSecretKey,
+    threshold: usize,
+    num_servers: usize,
+) -> Result<Vec<EpochKeyShare>, &'static str> {
+    if num_servers < threshold || threshold <= 1 {
+        return Err("invalid threshold");
+    }
+    secret_key
+        .split(threshold, num_servers)
+        .map_err(|_| "invalid threshold")
+}
+
+/// The head of an epoch, which links the accumulator value
+/// to the head of the previous epoch
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+pub struct EpochHead {
+    /// The epoch of the accumulator
+    pub epoch: usize,
+    /// The accumulator value at that epoch
+    pub accumulator: Accumulator,
+    /// The hash of the previous epoch's head, or all zeros for the first epoch
+    pub previous: [u8; 32],
+    /// Seconds since the Unix epoch when the head was created
+    pub timestamp: u64,
+}
+
+impl EpochHead {
+    /// The number of bytes in an epoch head
+    pub const BYTES: usize = 8 + Accumulator::BYTES + 32 + 8;
+
+    /// Creates the head for `accumulator` at `epoch`, following `previous`
+    pub fn new(epoch: usize, accumulator: Accumulator, previous: Option<&EpochHead>) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        Self {
+            epoch,
+            accumulator,
+            previous: previous.map(|h| h.hash()).unwrap_or_default(),
+            timestamp,
+        }
+    }
+
+    /// The hash that the next epoch's head links to
+    pub fn hash(&self) -> [u8; 32] {
+        let mut hasher = Sha3_256::new();
+        hasher.update(EPOCH_HEAD_DST);
+        hasher.update(self.to_bytes());
+        hasher.finalize().into()
+    }
+
+    /// Get the byte representation of the head
+    pub fn to_bytes(&self) -> [u8; Self::BYTES] {
+        let mut res = [0u8; Self::BYTES];
+        res[..8].copy_from_slice(&(self.epoch as u64).to_be_bytes());
+        res[8..56].copy_from_slice(&self.accumulator.to_bytes());
+        res[56..88].copy_from_slice(&self.previous);
+        res[88..].copy_from_slice(&self.timestamp.to_be_bytes());
+        res
+    }
+
+    /// Whether `self` directly follows `previous`
+    pub fn follows(&self, previous: &EpochHead) -> bool {
+        self.epoch == previous.epoch + 1
+            && self.previous == previous.hash()
+            && self.timestamp >= previous.timestamp
+    }
+
+    /// Whether both heads are for the same epoch but disagree on
+    /// the accumulator or on the history before it
+    pub fn conflicts_with(&self, other: &EpochHead) -> bool {
+        self.epoch == other.epoch
+            && (self.accumulator != other.accumulator || self.previous != other.previous)
+    }
+
+    /// Signs the head
+    pub fn sign(&self, secret_key: &EpochSecretKey) -> Result<SignedEpochHead, &'static str> {
+        let signature = secret_key
+            .sign(SignatureSchemes::ProofOfPossession, &self.message())
+            .map_err(|_| "signing failed")?;
+        Ok(SignedEpochHead {
+            head: *self,
+            signature,
+        })
+    }
+
+    /// Creates a server's partial signature on the head
+    pub fn sign_share(
+        &self,
+        key_share: &EpochKeyShare,
+    ) -> Result<EpochSignatureShare, &'static str> {
+        key_share
+            .sign(SignatureSchemes::ProofOfPossession, self.message())
+            .map_err(|_| "signing failed")
+    }
+
+    /// Checks a server's partial signature on the head
+    pub fn verify_share(
+        &self,
+        public_key_share: &EpochPublicKeyShare,
+        share: &EpochSignatureShare,
+    ) -> Result<(), &'static str> {
+        public_key_share
+            .verify(share, self.message())
+            .map_err(|_| "invalid signature share")
+    }
+
+    /// Combines a threshold of partial signatures into a certificate:
+    /// a single signature on the head under the joint key
+    pub fn certify(
+        &self,
+        shares: &[EpochSignatureShare],
+        joint_key: &EpochPublicKey,
+    ) -> Result<SignedEpochHead, &'static str> {
+        if shares.is_empty() {
+            return Err("no signature shares");
+        }
+        let signature =
+            EpochSignature::from_shares(shares).map_err(|_| "invalid signature share")?;
+        let certificate = SignedEpochHead {
+            head: *self,
+            signature,
+        };
+        certificate
+            .verify(joint_key)
+            .map_err(|_| "not signed by a threshold")?;
+        Ok(certificate)
+    }
+
+    fn message(&self) -> Vec<u8> {
+        let mut message = Vec::with_capacity(EPOCH_HEAD_DST.len() + Self::BYTES);
+        message.extend_from_slice(EPOCH_HEAD_DST);
+        message.extend_from_slice(&self.to_bytes());
+        message
+    }
+}
+
+/// An epoch head signed by a server,
+/// or certified by a threshold of servers under their joint key
+#[derive(Copy, Clone, Debug, Deserialize, Serialize)]
+pub struct SignedEpochHead {
+    /// The signed head
+    pub head: EpochHead,
+    /// The server's signature on the head
+    pub signature: EpochSignature,
+}
+
+impl SignedEpochHead {
+    /// Checks the signature against a server's public key
+    pub fn verify(&self, public_key: &EpochPublicKey) -> Result<(), &'static str> {
+        self.signature
+            .verify(public_key, self.head.message())
+            .map_err(|_| "invalid signature")
+    }
+
+    /// Returns the accumulator only if the head is validly signed
+    pub fn accumulator(&self, public_key: &EpochPublicKey) -> Result<Accumulator, &'static str> {
+        self.verify(public_key)?;
+        Ok(self.head.accumulator)
+    }
+
+    /// Checks that every head is validly signed and follows the one before it
+    pub fn verify_chain(
+        heads: &[SignedEpochHead],
+        public_key: &EpochPublicKey,
+    ) -> Result<(), &'static str> {
+        for head in heads {
+            head.verify(public_key)?;
+        }
+        if heads.windows(2).all(|w| w[1].head.follows(&w[0].head)) {
+            Ok(())
+        } else {
+            Err("broken epoch chain")
+        }
+    }
+
+    /// Returns the accumulator for `epoch` that at least `threshold` of the
+    /// `pinned` server keys have validly signed heads for.
+    /// Each pinned key counts once, however many heads it signed,
+    /// and heads under any other key are ignored
+    pub fn threshold_accumulator(
+        heads: &[SignedEpochHead],
+        pinned: &[EpochPublicKey],
+        epoch: usize,
+        threshold: usize,
+    ) -> Result<Accumulator, &'static str> {
+        if threshold == 0 {
+            return Err("invalid threshold");
+        }
+        let valid: Vec<Accumulator> = pinned
+            .iter()
+            .enumerate()
+            .filter(|(i, pk)| !pinned[..*i].contains(pk))
+            .filter_map(|(_, pk)| {
+                heads
+                    .iter()
+                    .find(|s| s.head.epoch == epoch && s.verify(pk).is_ok())
+                    .map(|s| s.head.accumulator)
+            })
+            .collect();
+        valid
+            .iter()
+            .find(|a| valid.iter().filter(|b| b == a).count() >= threshold)
+            .copied()
+            .ok_or("accumulator not signed by a threshold")
+    }
+}
+
+/// Two validly signed heads under the same key that conflict for one epoch.
+/// Anyone with the public key can check it,
+/// so users and verifiers can pass it on as evidence
+#[derive(Copy, Clone, Debug, Deserialize, Serialize)]
+pub struct ForkProof {
+    /// One of the conflicting heads
+    pub first: SignedEpochHead,
+    /// The other conflicting head
+    pub second: SignedEpochHead,
+}
+
+impl ForkProof {
+    /// Creates a fork proof if the heads conflict and are both signed under `public_key`
+    pub fn new(
+        first: SignedEpochHead,
+        second: SignedEpochHead,
+        public_key: &EpochPublicKey,
+    ) -> Option<Self> {
+        let proof = Self { first, second };
+        proof.verify(public_key).then_some(proof)
+    }
+
+    /// Looks for a fork among heads collected from users, verifiers or servers
+    pub fn find(heads: &[SignedEpochHead], public_key: &EpochPublicKey) -> Option<Self> {
+        let valid: Vec<&SignedEpochHead> = heads
+            .iter()
+            .filter(|h| h.verify(public_key).is_ok())
+            .collect();
+        valid.iter().enumerate().find_map(|(i, first)| {
+            valid[i + 1..]
+                .iter()
+                .find(|second| first.head.conflicts_with(&second.head))
+                .map(|second| Self {
+                    first: **first,
+                    second: **second,
+                })
+        })
+    }
+
+    /// Checks that the heads conflict and are both signed under `public_key`
+    pub fn verify(&self, public_key: &EpochPublicKey) -> bool {
+        self.first.head.conflicts_with(&self.second.head)
+            && self.first.verify(public_key).is_ok()
+            && self.second.verify(public_key).is_ok()
+    }
+}
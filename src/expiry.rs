@@ -0,0 +1,217 @@
+use crate::accumulator::{generate_fr, schnorr, Accumulator, SALT};
+use crate::credential::{AttributePresentation, Credential};
+use crate::utils::{AccParams, PublicKeys, UserID, SECURITY_BYTES};
+use crate::witness::{Statement, Witness};
+use blsful::inner_types::*;
+use merlin::Transcript;
+use serde::{Deserialize, Serialize};
+
+/// The number of bits of the range proof on t - epoch - 1,
+/// so a credential can expire at most 2^32 epochs after the current one
+pub const EXPIRY_BITS: usize = 32;
+
+/// A presentation of a `Credential` that proves the expiry epoch t the server signed
+/// into it is later than the proof's epoch, without revealing t.
+/// With D = t - epoch - 1 = sum 2^ib_i, each bit commitment C_i = b_iX + r_iY
+/// has an OR proof that b_i is 0 or 1, and
+/// T_L = s_tX + s_rhoY - c(sum 2^iC_i + (epoch + 1)X)
+/// links the bits to the response s_t of the expiry
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ExpiryPresentation {
+    pub(crate) presentation: AttributePresentation,
+    pub(crate) bit_commitments: Vec<G1Projective>,
+    pub(crate) bit_challenges: Vec<Scalar>,
+    pub(crate) bit_responses: Vec<[Scalar; 2]>,
+    pub(crate) s_rho: Scalar,
+}
+
+impl ExpiryPresentation {
+    /// The underlying attribute presentation, which discloses no attributes
+    pub fn presentation(&self) -> &AttributePresentation {
+        &self.presentation
+    }
+
+    /// The epoch the credential is proven not to have expired at
+    pub fn epoch(&self) -> usize {
+        self.presentation.proof.epoch
+    }
+
+    /// Verifies the presentation and that the credential expires after `current_epoch`,
+    /// the verifier's epoch. Presentations made for any other epoch fail
+    pub fn verify(
+        &self,
+        params: &AccParams,
+        public_keys: &PublicKeys,
+        accumulator: &Accumulator,
+        current_epoch: usize,
+        ephemeral_challenge: &[u8; 2 * SECURITY_BYTES],
+    ) -> bool {
+        if self.epoch() != current_epoch
+            || self.bit_commitments.len() != EXPIRY_BITS
+            || self.bit_challenges.len() != EXPIRY_BITS
+            || self.bit_responses.len() != EXPIRY_BITS
+        {
+            return false;
+        }
+        let c = self.presentation.proof.challenge;
+        let bit_proofs: Vec<[G1Projective; 2]> = (0..EXPIRY_BITS)
+            .map(|i| {
+                or_commitments(
+                    params,
+                    &self.bit_commitments[i],
+                    [self.bit_challenges[i], c - self.bit_challenges[i]],
+                    &self.bit_responses[i],
+                )
+            })
+            .collect();
+        // T_L = s_tX + s_rhoY - c(sum 2^iC_i + (epoch + 1)X)
+        let t_l = params.get_x1() * self.presentation.s_t + params.get_y1() * self.s_rho
+            - (sum_of_bits(&self.bit_commitments)
+                + params.get_x1() * Scalar::from(current_epoch as u64 + 1))
+                * c;
+        self.presentation
+            .check_bound(params, public_keys, accumulator, |transcript| {
+                bind(
+                    transcript,
+                    current_epoch,
+                    &self.bit_commitments,
+                    &bit_proofs,
+                    &t_l,
+                    ephemeral_challenge,
+                )
+            })
+    }
+}
+
+impl Witness {
+    /// Constructs a presentation of `credential` that hides all attributes
+    /// and proves its expiry is later than the statement's epoch.
+    /// Fails if the credential has expired or expires too far in the future
+    pub fn make_expiry_presentation(
+        witness: &Witness,
+        user_id: &UserID,
+        statement: &Statement,
+        params: &AccParams,
+        credential: &Credential,
+        ephemeral_challenge: &[u8; 2 * SECURITY_BYTES],
+    ) -> Option<ExpiryPresentation> {
+        let epoch = statement.epoch;
+        // D = t - epoch - 1 must fit in the range proof
+        let distance = credential.expiry.checked_sub(epoch as u64 + 1)?;
+        if distance >> EXPIRY_BITS != 0 {
+            return None;
+        }
+
+        // C_i = b_iX + r_iY, with the branch for 1 - b_i simulated
+        let rng = rand::rngs::OsRng;
+        let bits: Vec<usize> = (0..EXPIRY_BITS)
+            .map(|i| ((distance >> i) & 1) as usize)
+            .collect();
+        let r: Vec<Scalar> = bits.iter().map(|_| generate_fr(SALT, None, rng)).collect();
+        let w: Vec<Scalar> = bits.iter().map(|_| generate_fr(SALT, None, rng)).collect();
+        let commitments: Vec<G1Projective> = bits
+            .iter()
+            .zip(&r)
+            .map(|(b, r_i)| params.get_x1() * Scalar::from(*b as u64) + params.get_y1() * r_i)
+            .collect();
+        let mut bit_challenges = [(); EXPIRY_BITS].map(|_| generate_fr(SALT, None, rng));
+        let mut bit_responses =
+            [(); EXPIRY_BITS].map(|_| [generate_fr(SALT, None, rng), generate_fr(SALT, None, rng)]);
+        let bit_proofs: Vec<[G1Projective; 2]> = (0..EXPIRY_BITS)
+            .map(|i| {
+                let mut challenges = [Scalar::ZERO; 2];
+                challenges[1 - bits[i]] = bit_challenges[i];
+                let mut a = or_commitments(params, &commitments[i], challenges, &bit_responses[i]);
+                a[bits[i]] = params.get_y1() * w[i];
+                a
+            })
+            .collect();
+
+        // rho = sum 2^ir_i, so that sum 2^iC_i = DX + rhoY
+        let rho = sum_of_bits(&r);
+        let k_rho = generate_fr(SALT, None, rng);
+        let presentation = Self::present(
+            witness,
+            user_id,
+            statement,
+            params,
+            credential,
+            &[],
+            |transcript, k_t| {
+                // T_L = k_tX + k_rhoY
+                let t_l = params.get_x1() * k_t + params.get_y1() * k_rho;
+                bind(
+                    transcript,
+                    epoch,
+                    &commitments,
+                    &bit_proofs,
+                    &t_l,
+                    ephemeral_challenge,
+                )
+            },
+        )?;
+
+        // Answer the real branch of every bit with the rest of the challenge
+        let c = presentation.proof.challenge;
+        for i in 0..EXPIRY_BITS {
+            let simulated = bit_challenges[i];
+            let real = c - simulated;
+            bit_responses[i][bits[i]] = schnorr(w[i], r[i], real);
+            // Stored as the challenge of branch 0
+            bit_challenges[i] = if bits[i] == 0 { real } else { simulated };
+        }
+        Some(ExpiryPresentation {
+            presentation,
+            bit_commitments: commitments,
+            bit_challenges: bit_challenges.to_vec(),
+            bit_responses: bit_responses.to_vec(),
+            s_rho: schnorr(k_rho, rho, c),
+        })
+    }
+}
+
+// The OR proof commitments of a bit commitment C for challenges (c_0, c_1):
+// A_0 = z_0Y - c_0C and A_1 = z_1Y - c_1(C - X)
+fn or_commitments(
+    params: &AccParams,
+    commitment: &G1Projective,
+    challenges: [Scalar; 2],
+    responses: &[Scalar; 2],
+) -> [G1Projective; 2] {
+    [
+        params.get_y1() * responses[0] - commitment * challenges[0],
+        params.get_y1() * responses[1] - (commitment - params.get_x1()) * challenges[1],
+    ]
+}
+
+// sum 2^iv_i
+fn sum_of_bits<T>(values: &[T]) -> T
+where
+    T: Copy + core::ops::Add<Output = T> + core::ops::Mul<Scalar, Output = T>,
+{
+    values
+        .iter()
+        .rev()
+        .copied()
+        .reduce(|sum, v| sum * Scalar::from(2u64) + v)
+        .expect("at least one bit")
+}
+
+// Adds the expiry statement, bit commitments and their proofs to the transcript
+fn bind(
+    transcript: &mut Transcript,
+    epoch: usize,
+    commitments: &[G1Projective],
+    bit_proofs: &[[G1Projective; 2]],
+    t_l: &G1Projective,
+    ephemeral_challenge: &[u8; 2 * SECURITY_BYTES],
+) {
+    transcript.append_message(b"Current epoch", &(epoch as u64).to_be_bytes());
+    for (c_i, [a_0, a_1]) in commitments.iter().zip(bit_proofs) {
+        transcript.append_message(b"C_i", &c_i.to_compressed());
+        transcript.append_message(b"A_0", &a_0.to_compressed());
+        transcript.append_message(b"A_1", &a_1.to_compressed());
+    }
+    transcript.append_message(b"T_L", &t_l.to_compressed());
+    transcript.append_message(b"Ephemeral challenge", ephemeral_challenge);
+}
@@ -0,0 +1,138 @@
+use crate::accumulator::{generate_fr, Element, ProofMessage, SALT};
+use crate::utils::{AccParams, UserID, SECURITY_BYTES};
+use crate::witness::{
+    membership_transcript, MembershipProof, MembershipProofCommitting, Statement, Witness,
+};
+use merlin::Transcript;
+use serde::{Deserialize, Serialize};
+
+/// A user's witness from one issuer, to be proven in a `JointMembershipProof`
+#[derive(Copy, Clone, Debug)]
+pub struct IssuerWitness<'a> {
+    /// The witness and long-term signature from the issuer
+    pub witness: &'a Witness,
+    /// The issuer's public keys, and the accumulator and epoch the witness is valid for
+    pub statement: Statement<'a>,
+}
+
+/// One proof of membership in the accumulators of several issuers
+/// for the same hidden user ID y.
+/// All parts answer one challenge, and all blind y with the same k_7,
+/// so equal s_7 responses show that y is the same.
+/// A verifier checks it as one presentation instead of one per issuer
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct JointMembershipProof {
+    pub(crate) proofs: Vec<MembershipProof>,
+}
+
+impl JointMembershipProof {
+    /// The number of issuers in the proof
+    pub fn len(&self) -> usize {
+        self.proofs.len()
+    }
+
+    /// Whether the proof covers no issuers
+    pub fn is_empty(&self) -> bool {
+        self.proofs.is_empty()
+    }
+
+    /// The epochs of the issuers' accumulators, in the order they were proven
+    pub fn epochs(&self) -> Vec<usize> {
+        self.proofs.iter().map(|p| p.epoch).collect()
+    }
+
+    /// Verifies the proof against the issuers' public keys, accumulators and epochs,
+    /// in the order the user proved them.
+    /// Fails if any part was made for another epoch than its issuer's
+    pub fn verify(
+        &self,
+        params: &AccParams,
+        issuers: &[Statement],
+        ephemeral_challenge: &[u8; 2 * SECURITY_BYTES],
+    ) -> bool {
+        let first = match self.proofs.first() {
+            Some(first) => first,
+            None => return false,
+        };
+        if self.proofs.len() != issuers.len()
+            || self.proofs.iter().zip(issuers).any(|(p, issuer)| {
+                p.epoch != issuer.epoch || p.challenge != first.challenge || p.s_7 != first.s_7
+            })
+        {
+            return false;
+        }
+        let mut transcript = Transcript::new(b"joint_membership_proof");
+        for (proof, issuer) in self.proofs.iter().zip(issuers) {
+            let mut part =
+                membership_transcript(params, issuer.public_keys, issuer.accumulator, issuer.epoch);
+            proof.get_bytes_for_challenge(
+                params,
+                issuer.public_keys,
+                issuer.accumulator,
+                &mut part,
+            );
+            add_part(&mut transcript, &mut part);
+        }
+        transcript.append_message(b"Ephemeral challenge", ephemeral_challenge);
+        Element::from_transcript(b"challenge", &mut transcript).0 == first.challenge
+    }
+}
+
+impl Witness {
+    /// Constructs one proof of membership under all `issuers` for `user_id`,
+    /// showing that the hidden ID is the same in each
+    pub fn make_joint_membership_proof(
+        issuers: &[IssuerWitness],
+        user_id: &UserID,
+        params: &AccParams,
+        ephemeral_challenge: &[u8; 2 * SECURITY_BYTES],
+    ) -> Option<JointMembershipProof> {
+        if issuers.is_empty() {
+            return None;
+        }
+        // Share the blinding k_7 of y across the issuers
+        let y = ProofMessage::SharedBlinding {
+            message: user_id.0,
+            blinder: generate_fr(SALT, None, rand::rngs::OsRng),
+        };
+        let mut transcript = Transcript::new(b"joint_membership_proof");
+        let mut committed = Vec::with_capacity(issuers.len());
+        for IssuerWitness { witness, statement } in issuers {
+            Self::verify(
+                statement.accumulator,
+                statement.public_keys,
+                params,
+                user_id,
+                witness,
+            )
+            .ok()?;
+            let mpc = MembershipProofCommitting::new(witness, y, params, statement.public_keys);
+            let mut part = membership_transcript(
+                params,
+                statement.public_keys,
+                statement.accumulator,
+                statement.epoch,
+            );
+            mpc.get_bytes_for_challenge(&mut part);
+            add_part(&mut transcript, &mut part);
+            committed.push(mpc);
+        }
+        transcript.append_message(b"Ephemeral challenge", ephemeral_challenge);
+        let challenge = Element::from_transcript(b"challenge", &mut transcript);
+        let proofs = committed
+            .iter()
+            .zip(issuers)
+            .map(|(mpc, issuer)| {
+                mpc.gen_proof(issuer.witness, user_id, issuer.statement.epoch, challenge)
+            })
+            .collect();
+        Some(JointMembershipProof { proofs })
+    }
+}
+
+// Adds the digest of one issuer's proof transcript to the joint transcript
+fn add_part(transcript: &mut Transcript, part: &mut Transcript) {
+    let mut digest = [0u8; 2 * SECURITY_BYTES];
+    part.challenge_bytes(b"Issuer digest", &mut digest);
+    transcript.append_message(b"Issuer", &digest);
+}
@@ -0,0 +1,224 @@
+use crate::accumulator::{generate_fr, schnorr, Accumulator, SALT};
+use crate::utils::{AccParams, PublicKeys, UserID, SECURITY_BYTES};
+use crate::witness::{MembershipProof, Statement, Witness};
+use blsful::inner_types::*;
+use merlin::Transcript;
+use serde::{Deserialize, Serialize};
+
+/// A service's limit of k anonymous presentations per period.
+/// Each presentation reveals a serial number H(scope, period)^{1/(sk + j + 1)}
+/// for a counter j < k, so a user has exactly k serials per period:
+/// a user who presents more than k times must repeat a serial,
+/// which the service detects, while users within the limit stay unlinkable
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+pub struct UsageLimit {
+    /// The service the limit applies to
+    pub scope: Vec<u8>,
+    /// The current period, e.g. the number of the day
+    pub period: u64,
+    /// The number of presentations allowed per period
+    pub k: usize,
+}
+
+impl UsageLimit {
+    /// A limit of `k` presentations per period for `scope`
+    pub fn new(scope: &[u8], period: u64, k: usize) -> Self {
+        Self {
+            scope: scope.to_vec(),
+            period,
+            k,
+        }
+    }
+
+    // H(scope, period)
+    fn base(&self) -> G1Projective {
+        const DST: &[u8] = b"ALLOSAUR_K_TIMES_BLS12381G1_XMD:SHA-256_SSWU_RO_";
+        let mut msg = self.period.to_be_bytes().to_vec();
+        msg.extend_from_slice(&self.scope);
+        G1Projective::hash::<ExpandMsgXmd<sha2::Sha256>>(&msg, DST)
+    }
+}
+
+/// A membership proof that also reveals a k-times serial number.
+/// Besides the membership proof it shows that
+/// S = H/(sk + j + 1) for the same secret key,
+/// C = jX + rhoY commits to the same j,
+/// and C - vX = rhoY for some v in 0..k
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct KTimesPresentation {
+    pub(crate) proof: MembershipProof,
+    pub(crate) serial: G1Projective,
+    pub(crate) counter_commitment: G1Projective,
+    pub(crate) s_j: Scalar,
+    pub(crate) s_rho: Scalar,
+    pub(crate) or_challenges: Vec<Scalar>,
+    pub(crate) or_responses: Vec<Scalar>,
+}
+
+impl KTimesPresentation {
+    /// The serial number; a repeated serial within a period means the limit was exceeded
+    pub fn serial(&self) -> [u8; 48] {
+        self.serial.to_compressed()
+    }
+
+    /// The underlying membership proof
+    pub fn proof(&self) -> MembershipProof {
+        self.proof
+    }
+
+    /// Verifies the presentation for `limit` and the verifier's ephemeral challenge
+    pub fn verify(
+        &self,
+        params: &AccParams,
+        public_keys: &PublicKeys,
+        accumulator: &Accumulator,
+        limit: &UsageLimit,
+        ephemeral_challenge: &[u8; 2 * SECURITY_BYTES],
+    ) -> bool {
+        if bool::from(self.serial.is_identity())
+            || self.or_challenges.len() != limit.k
+            || self.or_responses.len() != limit.k
+        {
+            return false;
+        }
+        let c = self.proof.challenge;
+        if self.or_challenges.iter().sum::<Scalar>() != c {
+            return false;
+        }
+        let base = limit.base();
+        // T_S = (s_0 + s_j)S - c(H - S)
+        let t_s = self.serial * (self.proof.s_0 + self.s_j) - (base - self.serial) * c;
+        // T_C = s_jX + s_rhoY - cC
+        let t_c =
+            params.get_x1() * self.s_j + params.get_y1() * self.s_rho - self.counter_commitment * c;
+        // A_v = z_vY - c_v(C - vX)
+        let or_commitments = or_commitments(
+            params,
+            &self.counter_commitment,
+            &self.or_challenges,
+            &self.or_responses,
+        );
+        Witness::check_bound(
+            &self.proof,
+            params,
+            public_keys,
+            accumulator,
+            |transcript| {
+                bind(
+                    transcript,
+                    limit,
+                    ephemeral_challenge,
+                    &[self.serial, self.counter_commitment, t_s, t_c],
+                    &or_commitments,
+                )
+            },
+        )
+    }
+}
+
+impl Witness {
+    /// Constructs a k-times presentation for the `counter`-th use in `limit`'s period.
+    /// Fails if the counter is not below k
+    pub fn make_k_times_presentation(
+        witness: &Witness,
+        user_id: &UserID,
+        statement: &Statement,
+        params: &AccParams,
+        limit: &UsageLimit,
+        counter: usize,
+        ephemeral_challenge: &[u8; 2 * SECURITY_BYTES],
+    ) -> Option<KTimesPresentation> {
+        if counter >= limit.k {
+            return None;
+        }
+        let rng = rand::rngs::OsRng;
+        let j = Scalar::from(counter as u64);
+        let base = limit.base();
+        // S = H/(sk + j + 1)
+        let serial =
+            base * Option::<Scalar>::from((witness.secret_key.0 + j + Scalar::ONE).invert())?;
+
+        // C = jX + rhoY
+        let rho = generate_fr(SALT, None, rng);
+        let counter_commitment = params.get_x1() * j + params.get_y1() * rho;
+        let k_j = generate_fr(SALT, None, rng);
+        let k_rho = generate_fr(SALT, None, rng);
+        let t_c = params.get_x1() * k_j + params.get_y1() * k_rho;
+
+        // Simulate every branch of the OR proof but the counter's
+        let w = generate_fr(SALT, None, rng);
+        let mut or_challenges: Vec<Scalar> =
+            (0..limit.k).map(|_| generate_fr(SALT, None, rng)).collect();
+        let mut or_responses: Vec<Scalar> =
+            (0..limit.k).map(|_| generate_fr(SALT, None, rng)).collect();
+        or_challenges[counter] = Scalar::ZERO;
+        let mut or_commitments =
+            or_commitments(params, &counter_commitment, &or_challenges, &or_responses);
+        or_commitments[counter] = params.get_y1() * w;
+
+        let proof = Self::prove_bound(witness, user_id, statement, params, |transcript, k| {
+            // T_S = (k_0 + k_j)S
+            let t_s = serial * (k[0] + k_j);
+            bind(
+                transcript,
+                limit,
+                ephemeral_challenge,
+                &[serial, counter_commitment, t_s, t_c],
+                &or_commitments,
+            )
+        })?;
+        let c = proof.challenge;
+        or_challenges[counter] = c - or_challenges.iter().sum::<Scalar>();
+        or_responses[counter] = schnorr(w, rho, or_challenges[counter]);
+        Some(KTimesPresentation {
+            proof,
+            serial,
+            counter_commitment,
+            s_j: schnorr(k_j, j, c),
+            s_rho: schnorr(k_rho, rho, c),
+            or_challenges,
+            or_responses,
+        })
+    }
+}
+
+// A_v = z_vY - c_v(C - vX)
+fn or_commitments(
+    params: &AccParams,
+    counter_commitment: &G1Projective,
+    challenges: &[Scalar],
+    responses: &[Scalar],
+) -> Vec<G1Projective> {
+    challenges
+        .iter()
+        .zip(responses)
+        .enumerate()
+        .map(|(v, (c_v, z_v))| {
+            params.get_y1() * z_v
+                - (counter_commitment - params.get_x1() * Scalar::from(v as u64)) * c_v
+        })
+        .collect()
+}
+
+// Adds the limit, the serial statement and the OR commitments to the proof transcript
+fn bind(
+    transcript: &mut Transcript,
+    limit: &UsageLimit,
+    ephemeral_challenge: &[u8; 2 * SECURITY_BYTES],
+    points: &[G1Projective; 4],
+    or_commitments: &[G1Projective],
+) {
+    transcript.append_message(b"Usage scope", &limit.scope);
+    transcript.append_message(b"Usage period", &limit.period.to_be_bytes());
+    transcript.append_message(b"Usage limit", &(limit.k as u64).to_be_bytes());
+    for (label, point) in [b"Serial".as_slice(), b"Counter commitment", b"T_S", b"T_C"]
+        .into_iter()
+        .zip(points)
+    {
+        transcript.append_message(label, point.to_bytes().as_ref());
+    }
+    for a in or_commitments {
+        transcript.append_message(b"OR commitment", a.to_bytes().as_ref());
+    }
+    transcript.append_message(b"Ephemeral challenge", ephemeral_challenge);
+}
@@ -14,16 +14,54 @@
     trivial_casts,
     trivial_numeric_casts
 )]
+mod audit;
+mod batch;
+mod credential;
+mod designated;
+mod epoch;
+mod expiry;
+mod joint;
+mod k_times;
+mod log;
+mod non_membership;
+mod pedersen;
+mod policy;
+mod presentation;
+mod range_update;
+mod receipt;
+mod scoped;
 mod servers;
+mod traceable;
+mod transport;
 mod user;
 mod utils;
+mod verifier;
 mod witness;
 
 #[cfg(test)]
 mod tests;
 
 pub mod accumulator;
+pub use audit::*;
+pub use batch::*;
+pub use credential::*;
+pub use designated::*;
+pub use epoch::*;
+pub use expiry::*;
+pub use joint::*;
+pub use k_times::*;
+pub use log::*;
+pub use non_membership::*;
+pub use pedersen::*;
+pub use policy::*;
+pub use presentation::*;
+pub use range_update::*;
+pub use receipt::*;
+pub use scoped::*;
 pub use servers::*;
+pub use traceable::*;
+pub use transport::*;
 pub use user::*;
 pub use utils::*;
+pub use verifier::*;
 pub use witness::*;
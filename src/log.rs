@@ -0,0 +1,359 @@
+use crate::epoch::{EpochHead, EpochPublicKey, EpochSecretKey, EpochSignature};
+use blsful::SignatureSchemes;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Domain separation for signatures on log checkpoints
+const LOG_CHECKPOINT_DST: &[u8] = b"ALLOSAUR_LOG_CHECKPOINT";
+
+/// A hash in the transparency log
+pub type LogHash = [u8; 32];
+
+/// An append-only Merkle log of epoch heads, following RFC 6962.
+/// The head of epoch e is the leaf at index e - 1
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct TransparencyLog {
+    leaves: Vec<LogHash>,
+}
+
+impl TransparencyLog {
+    /// The number of epochs in the log
+    pub fn len(&self) -> usize {
+        self.leaves.len()
+    }
+
+    /// Whether the log has no epochs
+    pub fn is_empty(&self) -> bool {
+        self.leaves.is_empty()
+    }
+
+    /// Appends the head of the next epoch
+    pub fn append(&mut self, head: &EpochHead) -> Result<(), &'static str> {
+        if head.epoch != self.leaves.len() + 1 {
+            return Err("invalid epoch");
+        }
+        self.leaves.push(leaf_hash(head));
+        Ok(())
+    }
+
+    /// The root and size of the log
+    pub fn checkpoint(&self) -> LogCheckpoint {
+        LogCheckpoint {
+            size: self.leaves.len(),
+            root: tree_hash(&self.leaves),
+        }
+    }
+
+    /// The root and size of the log when it had `size` epochs
+    pub fn checkpoint_at(&self, size: usize) -> Option<LogCheckpoint> {
+        let leaves = self.leaves.get(..size)?;
+        Some(LogCheckpoint {
+            size,
+            root: tree_hash(leaves),
+        })
+    }
+
+    /// Proves that the head of `epoch` is in the log when it had `size` epochs
+    pub fn inclusion_proof(&self, epoch: usize, size: usize) -> Option<InclusionProof> {
+        if epoch == 0 || epoch > size {
+            return None;
+        }
+        let leaves = self.leaves.get(..size)?;
+        Some(InclusionProof {
+            epoch,
+            path: inclusion_path(epoch - 1, leaves),
+        })
+    }
+
+    /// Proves that the log at `old_size` epochs is a prefix of the log at `new_size` epochs
+    pub fn consistency_proof(&self, old_size: usize, new_size: usize) -> Option<ConsistencyProof> {
+        if old_size > new_size {
+            return None;
+        }
+        let leaves = self.leaves.get(..new_size)?;
+        let path = if old_size == 0 {
+            Vec::new()
+        } else {
+            consistency_path(old_size, leaves, true)
+        };
+        Some(ConsistencyProof { path })
+    }
+}
+
+/// The root of the log at a given size
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+pub struct LogCheckpoint {
+    /// The number of epochs in the log
+    pub size: usize,
+    /// The Merkle root of the log
+    pub root: LogHash,
+}
+
+impl LogCheckpoint {
+    /// The number of bytes in a checkpoint
+    pub const BYTES: usize = 8 + 32;
+
+    /// Get the byte representation of the checkpoint
+    pub fn to_bytes(&self) -> [u8; Self::BYTES] {
+        let mut res = [0u8; Self::BYTES];
+        res[..8].copy_from_slice(&(self.size as u64).to_be_bytes());
+        res[8..].copy_from_slice(&self.root);
+        res
+    }
+
+    /// Whether both checkpoints are for the same size but have different roots
+    pub fn conflicts_with(&self, other: &LogCheckpoint) -> bool {
+        self.size == other.size && self.root != other.root
+    }
+
+    /// Signs the checkpoint with the server's epoch key
+    pub fn sign(&self, secret_key: &EpochSecretKey) -> Result<SignedLogCheckpoint, &'static str> {
+        let signature = secret_key
+            .sign(SignatureSchemes::ProofOfPossession, &self.message())
+            .map_err(|_| "signing failed")?;
+        Ok(SignedLogCheckpoint {
+            checkpoint: *self,
+            signature,
+        })
+    }
+
+    fn message(&self) -> Vec<u8> {
+        let mut message = Vec::with_capacity(LOG_CHECKPOINT_DST.len() + Self::BYTES);
+        message.extend_from_slice(LOG_CHECKPOINT_DST);
+        message.extend_from_slice(&self.to_bytes());
+        message
+    }
+}
+
+/// A log checkpoint signed by a server with its epoch key,
+/// so the server cannot deny having published it
+#[derive(Copy, Clone, Debug, Deserialize, Serialize)]
+pub struct SignedLogCheckpoint {
+    /// The signed checkpoint
+    pub checkpoint: LogCheckpoint,
+    /// The server's signature on the checkpoint
+    pub signature: EpochSignature,
+}
+
+impl SignedLogCheckpoint {
+    /// Checks the signature against a server's public key
+    pub fn verify(&self, public_key: &EpochPublicKey) -> Result<(), &'static str> {
+        self.signature
+            .verify(public_key, self.checkpoint.message())
+            .map_err(|_| "invalid signature")
+    }
+}
+
+/// Two checkpoints signed under the same key for the same log size but with different roots.
+/// Like a `ForkProof` of conflicting heads, anyone with the public key can check it
+#[derive(Copy, Clone, Debug, Deserialize, Serialize)]
+pub struct LogForkProof {
+    /// One of the conflicting checkpoints
+    pub first: SignedLogCheckpoint,
+    /// The other conflicting checkpoint
+    pub second: SignedLogCheckpoint,
+}
+
+impl LogForkProof {
+    /// Creates a fork proof if the checkpoints conflict and are both signed under `public_key`
+    pub fn new(
+        first: SignedLogCheckpoint,
+        second: SignedLogCheckpoint,
+        public_key: &EpochPublicKey,
+    ) -> Option<Self> {
+        let proof = Self { first, second };
+        proof.verify(public_key).then_some(proof)
+    }
+
+    /// Checks that the checkpoints conflict and are both signed under `public_key`
+    pub fn verify(&self, public_key: &EpochPublicKey) -> bool {
+        self.first
+            .checkpoint
+            .conflicts_with(&self.second.checkpoint)
+            && self.first.verify(public_key).is_ok()
+            && self.second.verify(public_key).is_ok()
+    }
+}
+
+/// A proof that an epoch head is in the log
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct InclusionProof {
+    /// The epoch of the head
+    pub epoch: usize,
+    /// The sibling hashes from the leaf to the root
+    pub path: Vec<LogHash>,
+}
+
+impl InclusionProof {
+    /// Checks that `head` is in the log at `checkpoint`
+    pub fn verify(&self, head: &EpochHead, checkpoint: &LogCheckpoint) -> bool {
+        if head.epoch != self.epoch || self.epoch == 0 || self.epoch > checkpoint.size {
+            return false;
+        }
+        // RFC 9162, section 2.1.3.2
+        let mut f_n = self.epoch - 1;
+        let mut s_n = checkpoint.size - 1;
+        let mut r = leaf_hash(head);
+        for p in &self.path {
+            if s_n == 0 {
+                return false;
+            }
+            if f_n & 1 == 1 || f_n == s_n {
+                r = node_hash(p, &r);
+                while f_n & 1 == 0 && f_n != 0 {
+                    f_n >>= 1;
+                    s_n >>= 1;
+                }
+            } else {
+                r = node_hash(&r, p);
+            }
+            f_n >>= 1;
+            s_n >>= 1;
+        }
+        s_n == 0 && r == checkpoint.root
+    }
+}
+
+/// A proof that an older log is a prefix of a newer log
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ConsistencyProof {
+    /// The hashes needed to rebuild both roots
+    pub path: Vec<LogHash>,
+}
+
+impl ConsistencyProof {
+    /// Checks that both checkpoints are signed under `public_key`
+    /// and that the log at `old` is a prefix of the log at `new`
+    pub fn verify(
+        &self,
+        old: &SignedLogCheckpoint,
+        new: &SignedLogCheckpoint,
+        public_key: &EpochPublicKey,
+    ) -> bool {
+        old.verify(public_key).is_ok()
+            && new.verify(public_key).is_ok()
+            && self.extends(&old.checkpoint, &new.checkpoint)
+    }
+
+    // Checks the proof path from the old root to the new one
+    fn extends(&self, old: &LogCheckpoint, new: &LogCheckpoint) -> bool {
+        if old.size > new.size {
+            return false;
+        }
+        if old.size == 0 {
+            return self.path.is_empty();
+        }
+        if old.size == new.size {
+            return self.path.is_empty() && old.root == new.root;
+        }
+        // RFC 9162, section 2.1.4.2
+        let mut path = self.path.clone();
+        if old.size.is_power_of_two() {
+            path.insert(0, old.root);
+        }
+        let (first, rest) = match path.split_first() {
+            Some(split) => split,
+            None => return false,
+        };
+        let mut f_n = old.size - 1;
+        let mut s_n = new.size - 1;
+        while f_n & 1 == 1 {
+            f_n >>= 1;
+            s_n >>= 1;
+        }
+        let mut f_r = *first;
+        let mut s_r = *first;
+        for c in rest {
+            if s_n == 0 {
+                return false;
+            }
+            if f_n & 1 == 1 || f_n == s_n {
+                f_r = node_hash(c, &f_r);
+                s_r = node_hash(c, &s_r);
+                while f_n & 1 == 0 && f_n != 0 {
+                    f_n >>= 1;
+                    s_n >>= 1;
+                }
+            } else {
+                s_r = node_hash(&s_r, c);
+            }
+            f_n >>= 1;
+            s_n >>= 1;
+        }
+        f_r == old.root && s_r == new.root && s_n == 0
+    }
+}
+
+fn leaf_hash(head: &EpochHead) -> LogHash {
+    let mut hasher = Sha256::new();
+    hasher.update([0u8]);
+    hasher.update(head.to_bytes());
+    hasher.finalize().into()
+}
+
+fn node_hash(left: &LogHash, right: &LogHash) -> LogHash {
+    let mut hasher = Sha256::new();
+    hasher.update([1u8]);
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().into()
+}
+
+// The largest power of two smaller than n, for n > 1
+fn split_point(n: usize) -> usize {
+    1 << (usize::BITS - 1 - (n - 1).leading_zeros())
+}
+
+fn tree_hash(leaves: &[LogHash]) -> LogHash {
+    match leaves.len() {
+        0 => Sha256::digest([]).into(),
+        1 => leaves[0],
+        n => {
+            let k = split_point(n);
+            node_hash(&tree_hash(&leaves[..k]), &tree_hash(&leaves[k..]))
+        }
+    }
+}
+
+fn inclusion_path(index: usize, leaves: &[LogHash]) -> Vec<LogHash> {
+    let n = leaves.len();
+    if n <= 1 {
+        return Vec::new();
+    }
+    let k = split_point(n);
+    let (mut path, sibling) = if index < k {
+        (inclusion_path(index, &leaves[..k]), tree_hash(&leaves[k..]))
+    } else {
+        (
+            inclusion_path(index - k, &leaves[k..]),
+            tree_hash(&leaves[..k]),
+        )
+    };
+    path.push(sibling);
+    path
+}
+
+fn consistency_path(m: usize, leaves: &[LogHash], complete: bool) -> Vec<LogHash> {
+    let n = leaves.len();
+    if m == n {
+        return if complete {
+            Vec::new()
+        } else {
+            vec![tree_hash(leaves)]
+        };
+    }
+    let k = split_point(n);
+    let (mut path, sibling) = if m <= k {
+        (
+            consistency_path(m, &leaves[..k], complete),
+            tree_hash(&leaves[k..]),
+        )
+    } else {
+        (
+            consistency_path(m - k, &leaves[k..], false),
+            tree_hash(&leaves[..k]),
+        )
+    };
+    path.push(sibling);
+    path
+}
@@ -0,0 +1,446 @@
+use crate::accumulator::{
+    generate_fr, pair, schnorr, Accumulator, Element, NonMembershipWitness, SecretKey, SALT,
+};
+use crate::receipt::DeletionReceipt;
+use crate::utils::{g1, sc, usize_from, AccParams, PublicKeys, UserID, SECURITY_BYTES};
+use blsful::inner_types::*;
+use merlin::Transcript;
+use serde::{Deserialize, Serialize};
+
+/// The non-membership counterpart of `Witness`: shows that the user's ID
+/// is not among the deleted elements, bound to the same kind of long-term signature.
+/// With V_1 the accumulator of the first epoch and V_t the current one,
+/// the witness (C, d) satisfies C(y + alpha) + dV_t = V_1 with d != 0.
+/// Unlike a membership witness, it is updated from public deletion receipts
+/// without any help from the servers
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct NonWitness {
+    /// The user's secret key
+    pub secret_key: SecretKey,
+    /// The user's non-membership witness (C, d)
+    pub witness: NonMembershipWitness,
+    /// The user's BLS signature
+    pub signature: G1Projective,
+    /// The epoch the witness is valid for
+    pub epoch: usize,
+    /// The accumulator of that epoch V_t
+    pub accumulator: Accumulator,
+    /// The accumulator of the first epoch V_1
+    pub initial_accumulator: Accumulator,
+}
+
+impl NonWitness {
+    /// Verifies a witness directly, using the user's ID and their witness (including secret key)
+    pub fn verify(
+        &self,
+        public_keys: &PublicKeys,
+        params: &AccParams,
+        y: &UserID,
+    ) -> Result<(), &'static str> {
+        if bool::from(self.witness.d.is_zero()) {
+            return Err("deleted");
+        }
+        // e(C, yP + Q) * e(dV_t - V_1, P) = 1
+        let first_check = multi_miller_loop(&[
+            (
+                &self.witness.c.to_affine(),
+                &G2Prepared::from((params.get_p2() * y.0 + public_keys.witness_key.0).to_affine()),
+            ),
+            (
+                &(self.accumulator.0 * self.witness.d - self.initial_accumulator.0).to_affine(),
+                &G2Prepared::from(params.get_p2().to_affine()),
+            ),
+        ])
+        .final_exponentiation()
+        .is_identity();
+        // Same as for a membership witness
+        let second_check = multi_miller_loop(&[
+            (
+                &self.signature.to_affine(),
+                &G2Prepared::from((params.get_k2() * y.0 + public_keys.sign_key.0).to_affine()),
+            ),
+            (
+                &(params.get_k1() * self.secret_key.0 + params.get_k0()).to_affine(),
+                &G2Prepared::from(-params.get_k2().to_affine()),
+            ),
+        ])
+        .final_exponentiation()
+        .is_identity();
+        if (first_check & second_check).into() {
+            Ok(())
+        } else {
+            Err("failed")
+        }
+    }
+
+    /// Moves the witness forward through the deletion receipts that follow its epoch.
+    /// For a deletion of y' moving to V_{t+1}:
+    /// C' = C + dV_{t+1}
+    /// d' = d(y' - y)
+    /// Fails without changing the witness if the receipts skip an epoch,
+    /// do not start from the witness's accumulator, or delete the user
+    pub fn update(&mut self, y: &UserID, receipts: &[DeletionReceipt]) -> Result<(), &'static str> {
+        let mut updated = self.clone();
+        for receipt in receipts.iter().filter(|r| r.epoch > self.epoch) {
+            if receipt.epoch != updated.epoch + 1 || receipt.previous != updated.accumulator {
+                return Err("broken receipt chain");
+            }
+            if receipt.deleted == *y {
+                return Err("deleted");
+            }
+            updated.witness.c += receipt.accumulator.0 * updated.witness.d;
+            updated.witness.d *= receipt.deleted.0 - y.0;
+            updated.epoch = receipt.epoch;
+            updated.accumulator = receipt.accumulator;
+        }
+        *self = updated;
+        Ok(())
+    }
+
+    /// Constructs a zero-knowledge proof that the user holds a signed ID
+    /// that is not deleted as of the witness's epoch
+    pub fn make_non_membership_proof(
+        &self,
+        user_id: &UserID,
+        params: &AccParams,
+        public_keys: &PublicKeys,
+        ephemeral_challenge: &[u8; 2 * SECURITY_BYTES],
+    ) -> Option<NonMembershipProof> {
+        // Check that witness is valid
+        self.verify(public_keys, params, user_id).ok()?;
+
+        // Create commitment
+        let npc = NonMembershipProofCommitting::new(self, params, public_keys);
+
+        // Commit to public parameters
+        let mut transcript = non_membership_transcript(
+            params,
+            public_keys,
+            &self.initial_accumulator,
+            &self.accumulator,
+            self.epoch,
+        );
+        npc.get_bytes_for_challenge(&mut transcript);
+        transcript.append_message(b"Ephemeral challenge", ephemeral_challenge);
+        let challenge = Element::from_transcript(b"challenge", &mut transcript);
+        Some(npc.gen_proof(self, user_id, challenge))
+    }
+
+    /// Verifies a ZKPoK non-membership proof against the accumulators
+    /// of the first epoch and of the proof's epoch
+    pub fn check_non_membership_proof(
+        proof: &NonMembershipProof,
+        params: &AccParams,
+        public_keys: &PublicKeys,
+        initial_accumulator: &Accumulator,
+        accumulator: &Accumulator,
+        ephemeral_challenge: &[u8; 2 * SECURITY_BYTES],
+    ) -> bool {
+        let mut transcript = non_membership_transcript(
+            params,
+            public_keys,
+            initial_accumulator,
+            accumulator,
+            proof.epoch,
+        );
+        proof.get_bytes_for_challenge(
+            params,
+            public_keys,
+            initial_accumulator,
+            accumulator,
+            &mut transcript,
+        );
+        transcript.append_message(b"Ephemeral challenge", ephemeral_challenge);
+        let challenge = Element::from_transcript(b"challenge", &mut transcript);
+        challenge.0 == proof.challenge
+    }
+}
+
+// Starts a non-membership proof transcript with the public parameters and keys
+fn non_membership_transcript(
+    params: &AccParams,
+    public_keys: &PublicKeys,
+    initial_accumulator: &Accumulator,
+    accumulator: &Accumulator,
+    epoch: usize,
+) -> Transcript {
+    let mut transcript = Transcript::new(b"basic_non_membership_proof");
+    transcript.append_message(
+        b"Signature Public Key",
+        public_keys.witness_key.to_bytes().as_ref(),
+    );
+    transcript.append_message(
+        b"Witness Public Key",
+        public_keys.sign_key.to_bytes().as_ref(),
+    );
+    transcript.append_message(
+        b"Initial Accumulator",
+        initial_accumulator.to_bytes().as_ref(),
+    );
+    transcript.append_message(b"Accumulator", accumulator.to_bytes().as_ref());
+    transcript.append_u64(b"Epoch", epoch as u64);
+    params.add_to_transcript(&mut transcript);
+    transcript
+}
+
+/// The commit or blinding step for a non-membership proof.
+/// Extends the membership proof with d and a proof that d != 0:
+/// E = dX + rY is a commitment to d, and X = (1/d)E - (r/d)Y
+/// can only be shown if d != 0
+#[derive(Debug, Copy, Clone)]
+struct NonMembershipProofCommitting {
+    r: [Scalar; 4],
+    k: [Scalar; 12],
+    u_1: G1Projective,
+    u_2: G1Projective,
+    r_point: G1Projective,
+    e: G1Projective,
+    t_1: G1Projective,
+    t_2: G1Projective,
+    t_3: G1Projective,
+    t_4: G1Projective,
+    pi_1: Gt,
+    pi_2: Gt,
+}
+
+impl NonMembershipProofCommitting {
+    fn new(witness: &NonWitness, params: &AccParams, public_keys: &PublicKeys) -> Self {
+        let rng = rand::rngs::OsRng;
+        let r: [Scalar; 4] = [(); 4].map(|_| generate_fr(SALT, None, rng));
+        let k: [Scalar; 12] = [(); 12].map(|_| generate_fr(SALT, None, rng));
+
+        // U_1 = R_m + r_1Z
+        let u_1 = witness.signature + params.get_z1() * r[0];
+
+        // U_2 = C + r_2Z
+        let u_2 = witness.witness.c + params.get_z1() * r[1];
+
+        // R = r_1X + r_2Y + r_3Z
+        let r_point = params.get_x1() * r[0] + params.get_y1() * r[1] + params.get_z1() * r[2];
+
+        // E = dX + r_4Y
+        let e = params.get_x1() * witness.witness.d + params.get_y1() * r[3];
+
+        // T_1 = k_1X + k_2Y + k_3Z
+        let t_1 = params.get_x1() * k[1] + params.get_y1() * k[2] + params.get_z1() * k[3];
+
+        // T_2 = k_4X + k_5Y + k_6Z - k_7R
+        let t_2 = params.get_x1() * k[4] + params.get_y1() * k[5] + params.get_z1() * k[6]
+            - r_point * k[7];
+
+        // T_3 = k_8X + k_9Y
+        let t_3 = params.get_x1() * k[8] + params.get_y1() * k[9];
+
+        // T_4 = k_10E + k_11Y
+        let t_4 = e * k[10] + params.get_y1() * k[11];
+
+        // Pi_1 = e(k_0K - k_7U_1,K) * e(Z,k_4K + k_1Q_m)
+        let pi_1 = pair(params.get_k1() * k[0] - u_1 * k[7], params.get_k2())
+            + pair(
+                params.get_z1(),
+                params.get_k2() * k[4] + public_keys.sign_key.0 * k[1],
+            );
+        // Pi_2 = e(-k_7U_2 - k_8V_t + k_5Z,P) * e(Z,Q)^{k_2}
+        let pi_2 = pair(
+            params.get_z1() * k[5] - u_2 * k[7] - witness.accumulator.0 * k[8],
+            params.get_p2(),
+        ) + pair(params.get_z1(), public_keys.witness_key.0 * k[2]);
+
+        Self {
+            r,
+            k,
+            u_1,
+            u_2,
+            r_point,
+            e,
+            t_1,
+            t_2,
+            t_3,
+            t_4,
+            pi_1,
+            pi_2,
+        }
+    }
+
+    // U_1 || U_2 || R || E || T_1 || T_2 || T_3 || T_4 || Pi_1 || Pi_2
+    fn get_bytes_for_challenge(&self, transcript: &mut Transcript) {
+        transcript.append_message(b"U_1", &self.u_1.to_compressed());
+        transcript.append_message(b"U_2", &self.u_2.to_compressed());
+        transcript.append_message(b"R", &self.r_point.to_compressed());
+        transcript.append_message(b"E", &self.e.to_compressed());
+        transcript.append_message(b"T_1", &self.t_1.to_compressed());
+        transcript.append_message(b"T_2", &self.t_2.to_compressed());
+        transcript.append_message(b"T_3", &self.t_3.to_compressed());
+        transcript.append_message(b"T_4", &self.t_4.to_compressed());
+        transcript.append_message(b"Pi_1", self.pi_1.to_bytes().as_ref());
+        transcript.append_message(b"Pi_2", self.pi_2.to_bytes().as_ref());
+    }
+
+    fn gen_proof(
+        &self,
+        witness: &NonWitness,
+        user_id: &UserID,
+        challenge_hash: Element,
+    ) -> NonMembershipProof {
+        let c = challenge_hash.0;
+        let d = witness.witness.d;
+        let d_inv = d.invert().expect("a valid witness");
+
+        NonMembershipProof {
+            epoch: witness.epoch,
+            u_1: self.u_1,
+            u_2: self.u_2,
+            r: self.r_point,
+            e: self.e,
+            challenge: c,
+            s_0: schnorr(self.k[0], witness.secret_key.0, c),
+            s_1: schnorr(self.k[1], self.r[0], c),
+            s_2: schnorr(self.k[2], self.r[1], c),
+            s_3: schnorr(self.k[3], self.r[2], c),
+            s_4: schnorr(self.k[4], self.r[0] * user_id.0, c),
+            s_5: schnorr(self.k[5], self.r[1] * user_id.0, c),
+            s_6: schnorr(self.k[6], self.r[2] * user_id.0, c),
+            s_7: schnorr(self.k[7], user_id.0, c),
+            s_8: schnorr(self.k[8], d, c),
+            s_9: schnorr(self.k[9], self.r[3], c),
+            s_10: schnorr(self.k[10], d_inv, c),
+            s_11: schnorr(self.k[11], -(self.r[3] * d_inv), c),
+        }
+    }
+}
+
+/// A ZKP non-membership proof
+#[derive(Debug, Default, Copy, Clone, Deserialize, Serialize)]
+pub struct NonMembershipProof {
+    pub(crate) epoch: usize,
+    pub(crate) u_1: G1Projective,
+    pub(crate) u_2: G1Projective,
+    pub(crate) r: G1Projective,
+    pub(crate) e: G1Projective,
+    pub(crate) challenge: Scalar,
+    pub(crate) s_0: Scalar,
+    pub(crate) s_1: Scalar,
+    pub(crate) s_2: Scalar,
+    pub(crate) s_3: Scalar,
+    pub(crate) s_4: Scalar,
+    pub(crate) s_5: Scalar,
+    pub(crate) s_6: Scalar,
+    pub(crate) s_7: Scalar,
+    pub(crate) s_8: Scalar,
+    pub(crate) s_9: Scalar,
+    pub(crate) s_10: Scalar,
+    pub(crate) s_11: Scalar,
+}
+
+impl NonMembershipProof {
+    /// The size of the proof in bytes
+    pub const BYTES: usize = 616;
+
+    /// The epoch of the accumulator the proof was made against
+    pub fn epoch(&self) -> usize {
+        self.epoch
+    }
+
+    /// Get the byte representation of the proof
+    pub fn to_bytes(&self) -> [u8; Self::BYTES] {
+        let mut res = [0u8; Self::BYTES];
+        res[0..48].copy_from_slice(&self.u_1.to_compressed());
+        res[48..96].copy_from_slice(&self.u_2.to_compressed());
+        res[96..144].copy_from_slice(&self.r.to_compressed());
+        res[144..192].copy_from_slice(&self.e.to_compressed());
+        let scalars = [
+            self.challenge,
+            self.s_0,
+            self.s_1,
+            self.s_2,
+            self.s_3,
+            self.s_4,
+            self.s_5,
+            self.s_6,
+            self.s_7,
+            self.s_8,
+            self.s_9,
+            self.s_10,
+            self.s_11,
+        ];
+        for (chunk, s) in res[192..608].chunks_exact_mut(32).zip(scalars.iter()) {
+            chunk.copy_from_slice(&s.to_be_bytes());
+        }
+        res[608..616].copy_from_slice(&(self.epoch as u64).to_be_bytes());
+        res
+    }
+
+    /// Convert a byte representation to a proof
+    pub fn from_bytes(input: &[u8; Self::BYTES]) -> Result<Self, &'static str> {
+        let s = |i: usize| sc(&input[192 + 32 * i..224 + 32 * i]);
+        Ok(Self {
+            epoch: usize_from(&input[608..616])?,
+            u_1: g1(&input[0..48])?,
+            u_2: g1(&input[48..96])?,
+            r: g1(&input[96..144])?,
+            e: g1(&input[144..192])?,
+            challenge: s(0)?,
+            s_0: s(1)?,
+            s_1: s(2)?,
+            s_2: s(3)?,
+            s_3: s(4)?,
+            s_4: s(5)?,
+            s_5: s(6)?,
+            s_6: s(7)?,
+            s_7: s(8)?,
+            s_8: s(9)?,
+            s_9: s(10)?,
+            s_10: s(11)?,
+            s_11: s(12)?,
+        })
+    }
+
+    // Reconstructs the commitments from the responses
+    // and adds them to the transcript
+    fn get_bytes_for_challenge(
+        &self,
+        params: &AccParams,
+        public_keys: &PublicKeys,
+        initial_accumulator: &Accumulator,
+        accumulator: &Accumulator,
+        transcript: &mut Transcript,
+    ) {
+        let c = self.challenge;
+        let t_1 =
+            params.get_x1() * self.s_1 + params.get_y1() * self.s_2 + params.get_z1() * self.s_3
+                - self.r * c;
+        let t_2 =
+            params.get_x1() * self.s_4 + params.get_y1() * self.s_5 + params.get_z1() * self.s_6
+                - self.r * self.s_7;
+        let t_3 = params.get_x1() * self.s_8 + params.get_y1() * self.s_9 - self.e * c;
+        let t_4 = self.e * self.s_10 + params.get_y1() * self.s_11 - params.get_x1() * c;
+        let pi_1 = pair(
+            params.get_k1() * self.s_0 - self.u_1 * self.s_7
+                + params.get_z1() * self.s_4
+                + params.get_k0() * c,
+            params.get_k2(),
+        ) + pair(
+            params.get_z1() * self.s_1 - self.u_1 * c,
+            public_keys.sign_key.0,
+        );
+        let pi_2 = pair(
+            -self.u_2 * self.s_7 - accumulator.0 * self.s_8
+                + params.get_z1() * self.s_5
+                + initial_accumulator.0 * c,
+            params.get_p2(),
+        ) + pair(
+            params.get_z1() * self.s_2 - self.u_2 * c,
+            public_keys.witness_key.0,
+        );
+        transcript.append_message(b"U_1", &self.u_1.to_compressed());
+        transcript.append_message(b"U_2", &self.u_2.to_compressed());
+        transcript.append_message(b"R", &self.r.to_compressed());
+        transcript.append_message(b"E", &self.e.to_compressed());
+        transcript.append_message(b"T_1", &t_1.to_compressed());
+        transcript.append_message(b"T_2", &t_2.to_compressed());
+        transcript.append_message(b"T_3", &t_3.to_compressed());
+        transcript.append_message(b"T_4", &t_4.to_compressed());
+        transcript.append_message(b"Pi_1", pi_1.to_bytes().as_ref());
+        transcript.append_message(b"Pi_2", pi_2.to_bytes().as_ref());
+    }
+}
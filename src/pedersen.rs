@@ -0,0 +1,130 @@
+use crate::accumulator::{generate_fr, schnorr, Accumulator, SALT};
+use crate::utils::{AccParams, PublicKeys, UserID, SECURITY_BYTES};
+use crate::witness::{MembershipProof, Statement, Witness};
+use blsful::inner_types::*;
+use merlin::Transcript;
+use serde::{Deserialize, Serialize};
+
+/// The generators G and H of a Pedersen commitment C = yG + rH to a user ID.
+/// Use the generators of the external proof system that reasons about y
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+pub struct PedersenGenerators {
+    /// G, the generator of the committed value
+    pub g: G1Projective,
+    /// H, the generator of the blinding
+    pub h: G1Projective,
+}
+
+impl Default for PedersenGenerators {
+    fn default() -> Self {
+        const DST: &[u8] = b"ALLOSAUR_PEDERSEN_BLS12381G1_XMD:SHA-256_SSWU_RO_";
+        Self {
+            g: G1Projective::hash::<ExpandMsgXmd<sha2::Sha256>>(b"G", DST),
+            h: G1Projective::hash::<ExpandMsgXmd<sha2::Sha256>>(b"H", DST),
+        }
+    }
+}
+
+/// A membership proof that also outputs a Pedersen commitment C = yG + rH
+/// to the hidden user ID, with T_C = s_7G + s_rH - cC proving it commits
+/// to the same y as the membership proof.
+/// External proofs about C then reason about the accumulated y
+#[derive(Copy, Clone, Debug, Deserialize, Serialize)]
+pub struct PedersenMembershipProof {
+    pub(crate) proof: MembershipProof,
+    pub(crate) commitment: G1Projective,
+    pub(crate) s_r: Scalar,
+}
+
+impl PedersenMembershipProof {
+    /// The underlying membership proof
+    pub fn proof(&self) -> MembershipProof {
+        self.proof
+    }
+
+    /// The commitment C to the user ID
+    pub fn commitment(&self) -> G1Projective {
+        self.commitment
+    }
+
+    /// Verifies the proof and that the commitment holds the proven user ID
+    pub fn verify(
+        &self,
+        params: &AccParams,
+        public_keys: &PublicKeys,
+        accumulator: &Accumulator,
+        generators: &PedersenGenerators,
+        ephemeral_challenge: &[u8; 2 * SECURITY_BYTES],
+    ) -> bool {
+        // T_C = s_7G + s_rH - cC
+        let t_c = generators.g * self.proof.s_7 + generators.h * self.s_r
+            - self.commitment * self.proof.challenge;
+        Witness::check_bound(
+            &self.proof,
+            params,
+            public_keys,
+            accumulator,
+            |transcript| {
+                bind(
+                    transcript,
+                    generators,
+                    &self.commitment,
+                    &t_c,
+                    ephemeral_challenge,
+                )
+            },
+        )
+    }
+}
+
+impl Witness {
+    /// Constructs a membership proof with a Pedersen commitment to the user ID
+    /// under `generators`, returned along with the commitment's blinding r
+    pub fn make_pedersen_membership_proof(
+        witness: &Witness,
+        user_id: &UserID,
+        statement: &Statement,
+        params: &AccParams,
+        generators: &PedersenGenerators,
+        ephemeral_challenge: &[u8; 2 * SECURITY_BYTES],
+    ) -> Option<(PedersenMembershipProof, Scalar)> {
+        let rng = rand::rngs::OsRng;
+        let r = generate_fr(SALT, None, rng);
+        let k_r = generate_fr(SALT, None, rng);
+        let commitment = generators.g * user_id.0 + generators.h * r;
+        let proof = Self::prove_bound(witness, user_id, statement, params, |transcript, k| {
+            // T_C = k_7G + k_rH
+            let t_c = generators.g * k[7] + generators.h * k_r;
+            bind(
+                transcript,
+                generators,
+                &commitment,
+                &t_c,
+                ephemeral_challenge,
+            )
+        })?;
+        Some((
+            PedersenMembershipProof {
+                proof,
+                commitment,
+                s_r: schnorr(k_r, r, proof.challenge),
+            },
+            r,
+        ))
+    }
+}
+
+// Adds the generators, the commitment and T_C to the proof transcript
+fn bind(
+    transcript: &mut Transcript,
+    generators: &PedersenGenerators,
+    commitment: &G1Projective,
+    t_c: &G1Projective,
+    ephemeral_challenge: &[u8; 2 * SECURITY_BYTES],
+) {
+    transcript.append_message(b"Pedersen G", &generators.g.to_compressed());
+    transcript.append_message(b"Pedersen H", &generators.h.to_compressed());
+    transcript.append_message(b"Pedersen commitment", &commitment.to_compressed());
+    transcript.append_message(b"T_C", &t_c.to_compressed());
+    transcript.append_message(b"Ephemeral challenge", ephemeral_challenge);
+}
@@ -0,0 +1,128 @@
+use serde::{Deserialize, Serialize};
+
+/// Bytes of a scalar share sent from the user to a server
+const SCALAR_BYTES: usize = 32;
+/// Bytes of a (d, v) chunk share sent from a server to the user
+const CHUNK_BYTES: usize = 32 + 48;
+
+/// How a user trades communication against computation during an update.
+/// Every policy picks k, where the user sends k-1 shares of powers of y
+/// to each server and each server answers with one chunk per k-1 epochs
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub enum UpdatePolicy {
+    /// Balance upload and download, with a preference for upload
+    /// since it saves the user elliptic curve computations
+    #[default]
+    Balanced,
+    /// Minimize the total bytes sent and received
+    MinimizeBandwidth,
+    /// Minimize the user's elliptic curve operations by asking for a single chunk
+    MinimizeUserOperations,
+    /// Balance as above but send at most this many bytes to each server
+    MaxUploadBytes(usize),
+    /// Use this value of k, capped at d + 1
+    Explicit(usize),
+}
+
+impl UpdatePolicy {
+    /// Chooses k for an update over `epoch_diff` epochs
+    pub fn choose_k(&self, epoch_diff: usize) -> Result<usize, &'static str> {
+        if epoch_diff == 0 {
+            return Err("invalid epoch");
+        }
+        let d = epoch_diff;
+        match *self {
+            Self::Balanced => Ok(balanced_k(d)),
+            Self::MinimizeBandwidth => {
+                // Bytes per server are 32*(k-1) + 80*ceil(d/(k-1))
+                let k = (2..=d + 1)
+                    .min_by_key(|k| bytes_up(*k) + bytes_down(d, *k))
+                    .expect("a non-empty range");
+                Ok(k)
+            }
+            Self::MinimizeUserOperations => Ok(d + 1),
+            Self::MaxUploadBytes(bytes) => {
+                if bytes < SCALAR_BYTES {
+                    return Err("upload limit too small");
+                }
+                Ok(std::cmp::min(balanced_k(d), bytes / SCALAR_BYTES + 1))
+            }
+            Self::Explicit(k) => {
+                if k < 2 {
+                    return Err("invalid k");
+                }
+                // More than d + 1 only adds shares without saving a chunk
+                Ok(std::cmp::min(k, d + 1))
+            }
+        }
+    }
+}
+
+// We expect 32*(k-1) bytes user->server
+// and 80*ceil(d/k) bytes server->user
+// We want these to balance, with preference for user->server since it saves
+// the user elliptic curve computations
+fn balanced_k(d: usize) -> usize {
+    let mut k = ((d as f64) * 2.5).sqrt() as usize;
+    while 2 * k < 5 * (d + k - 1) / k {
+        k += 1;
+    }
+    k
+}
+
+fn bytes_up(k: usize) -> usize {
+    SCALAR_BYTES * (k - 1)
+}
+
+fn bytes_down(d: usize, k: usize) -> usize {
+    CHUNK_BYTES * d.div_ceil(k - 1)
+}
+
+/// The predicted cost of an update, known before any network traffic
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct UpdateCost {
+    /// The chosen value of k
+    pub k: usize,
+    /// The number of (d, v) chunks each server returns
+    pub chunks: usize,
+    /// Bytes of shares the user sends to each server
+    pub upload_bytes: usize,
+    /// Bytes of chunk shares each server sends to the user
+    pub download_bytes: usize,
+    /// G1 scalar multiplications the user runs to finish the update
+    pub user_point_multiplications: usize,
+    /// G1 scalar multiplications each server runs to answer
+    pub server_point_multiplications: usize,
+}
+
+impl UpdateCost {
+    /// Predicts the cost of updating over `epoch_diff` epochs
+    pub fn predict(
+        epoch_diff: usize,
+        num_servers: usize,
+        threshold: usize,
+        policy: UpdatePolicy,
+    ) -> Result<Self, &'static str> {
+        if num_servers < threshold || threshold <= 1 {
+            return Err("invalid threshold");
+        }
+        let k = policy.choose_k(epoch_diff)?;
+        let chunks = epoch_diff.div_ceil(k - 1);
+        // Each chunk is rebuilt from a threshold of shares,
+        // checked against one more share if available,
+        // and then applied to the witness
+        let per_chunk = if num_servers > threshold {
+            2 * threshold + 1
+        } else {
+            threshold + 1
+        };
+        Ok(Self {
+            k,
+            chunks,
+            upload_bytes: bytes_up(k),
+            download_bytes: bytes_down(epoch_diff, k),
+            user_point_multiplications: chunks * per_chunk,
+            server_point_multiplications: epoch_diff,
+        })
+    }
+}
@@ -0,0 +1,97 @@
+use crate::accumulator::Accumulator;
+use crate::utils::{AccParams, PublicKeys, UserID};
+use crate::witness::{MembershipProof, Statement, Witness};
+use merlin::Transcript;
+use serde::{Deserialize, Serialize};
+
+/// The domain label of presentations that do not set their own
+pub const DEFAULT_PRESENTATION_DOMAIN: &[u8] = b"ALLOSAUR_PRESENTATION";
+
+/// The application message a presentation signs.
+/// The domain label separates one application's presentations
+/// from another's, so a presentation made for one domain
+/// never verifies in another
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+pub struct PresentationMessage {
+    /// The domain separation label of the application
+    pub domain: Vec<u8>,
+    /// Who the presentation is meant for
+    pub audience: Vec<u8>,
+    /// A fresh value from the audience so presentations cannot be replayed
+    pub nonce: Vec<u8>,
+    /// The payload being signed, e.g. a request body
+    pub body: Vec<u8>,
+}
+
+impl Default for PresentationMessage {
+    fn default() -> Self {
+        Self::new(DEFAULT_PRESENTATION_DOMAIN)
+    }
+}
+
+impl PresentationMessage {
+    /// An empty message in the given domain
+    pub fn new(domain: &[u8]) -> Self {
+        Self {
+            domain: domain.to_vec(),
+            audience: Vec::new(),
+            nonce: Vec::new(),
+            body: Vec::new(),
+        }
+    }
+
+    /// Sets the audience
+    pub fn with_audience(mut self, audience: &[u8]) -> Self {
+        self.audience = audience.to_vec();
+        self
+    }
+
+    /// Sets the nonce
+    pub fn with_nonce(mut self, nonce: &[u8]) -> Self {
+        self.nonce = nonce.to_vec();
+        self
+    }
+
+    /// Sets the body
+    pub fn with_body(mut self, body: &[u8]) -> Self {
+        self.body = body.to_vec();
+        self
+    }
+
+    // Adds the whole message to the proof transcript
+    fn bind(&self, transcript: &mut Transcript) {
+        transcript.append_message(b"Presentation domain", &self.domain);
+        transcript.append_message(b"Audience", &self.audience);
+        transcript.append_message(b"Nonce", &self.nonce);
+        transcript.append_message(b"Body", &self.body);
+    }
+}
+
+impl Witness {
+    /// Constructs a membership proof that is also a signature of knowledge
+    /// on `message`: it only verifies for the same domain, audience, nonce and body
+    pub fn make_presentation(
+        witness: &Witness,
+        user_id: &UserID,
+        statement: &Statement,
+        params: &AccParams,
+        message: &PresentationMessage,
+    ) -> Option<MembershipProof> {
+        Self::prove_bound(witness, user_id, statement, params, |transcript, _| {
+            message.bind(transcript)
+        })
+    }
+
+    /// Verifies a presentation on `message`
+    pub fn check_presentation(
+        proof: &MembershipProof,
+        params: &AccParams,
+        public_keys: &PublicKeys,
+        accumulator: &Accumulator,
+        message: &PresentationMessage,
+    ) -> bool {
+        Self::check_bound(proof, params, public_keys, accumulator, |transcript| {
+            message.bind(transcript)
+        })
+    }
+}
@@ -0,0 +1,184 @@
+use crate::accumulator::{
+    evaluate_deltas, Accumulator, Coefficient, MembershipWitness, Polynomial,
+};
+use crate::utils::UserID;
+use blsful::inner_types::*;
+use serde::{Deserialize, Serialize};
+
+/// An aggregated update for the deletions in a contiguous range of epochs.
+/// Applying it moves a witness from `epoch` to `epoch + deletions.len()`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RangeUpdate {
+    /// The epoch this update starts from
+    pub epoch: usize,
+    /// The deleted IDs y_1,...,y_m in order of deletion
+    pub deletions: Vec<UserID>,
+    /// The batch update coefficients for the deletions,
+    /// as returned by `Accumulator::update_assign`
+    pub coefficients: Vec<Coefficient>,
+}
+
+impl RangeUpdate {
+    /// The update for a single deletion of `y`, which produced `new_accumulator`
+    pub fn new(epoch: usize, y: UserID, new_accumulator: &Accumulator) -> Self {
+        // C' = (C - V') * (1 / {y - y'})
+        Self {
+            epoch,
+            deletions: vec![y],
+            coefficients: vec![Coefficient(-new_accumulator.0)],
+        }
+    }
+
+    /// The epoch a witness is at after applying this update
+    pub fn end_epoch(&self) -> usize {
+        self.epoch + self.deletions.len()
+    }
+
+    /// Merges this update with the one directly following it
+    pub fn merge(&self, next: &RangeUpdate) -> Result<RangeUpdate, &'static str> {
+        if self.end_epoch() != next.epoch {
+            return Err("updates are not adjacent");
+        }
+        // dD(x) = ∏ 1..m (yD_i - x) for the first range
+        let mut d_poly = Polynomial::with_capacity(self.deletions.len() + 1);
+        d_poly.push(Scalar::ONE);
+        for y in &self.deletions {
+            d_poly *= &[y.0, -Scalar::ONE];
+        }
+
+        // Ω(x) = Ω_1(x) + dD_1(x)·Ω_2(x)
+        let points: Vec<G1Projective> = next.coefficients.iter().map(|c| c.0).collect();
+        let len = self.coefficients.len() + points.len();
+        let mut coefficients = Vec::with_capacity(len);
+        for k in 0..len {
+            let lo = k.saturating_sub(d_poly.degree());
+            let hi = std::cmp::min(k + 1, points.len());
+            let mut c = G1Projective::IDENTITY;
+            if lo < hi {
+                let scalars: Vec<Scalar> = (lo..hi).map(|j| d_poly.0[k - j]).collect();
+                c = G1Projective::sum_of_products(&points[lo..hi], &scalars);
+            }
+            if let Some(c_1) = self.coefficients.get(k) {
+                c += c_1.0;
+            }
+            coefficients.push(Coefficient(c));
+        }
+
+        let mut deletions = self.deletions.clone();
+        deletions.extend_from_slice(&next.deletions);
+        Ok(RangeUpdate {
+            epoch: self.epoch,
+            deletions,
+            coefficients,
+        })
+    }
+
+    /// Applies a chain of consecutive updates to a witness for `y`
+    pub fn apply(
+        y: UserID,
+        witness: MembershipWitness,
+        updates: &[RangeUpdate],
+    ) -> Result<MembershipWitness, &'static str> {
+        if updates.is_empty() {
+            return Ok(witness);
+        }
+        if updates.windows(2).any(|w| w[0].end_epoch() != w[1].epoch) {
+            return Err("updates are not adjacent");
+        }
+        let deltas: Vec<(&[UserID], &[UserID], &[Coefficient])> = updates
+            .iter()
+            .map(|u| (&[][..], u.deletions.as_slice(), u.coefficients.as_slice()))
+            .collect();
+        match evaluate_deltas(y, &deltas) {
+            Ok(delta) => Ok(witness.apply_delta(delta)),
+            Err(_) => Err("user has been deleted"),
+        }
+    }
+}
+
+/// A segment tree of aggregated updates over all aligned power-of-two ranges of epochs.
+/// A witness can catch up over any range of epochs with O(log) updates,
+/// plus one per 2^max_level epochs if the levels are capped
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct RangeUpdates {
+    /// levels[l][i] covers the deletions i*2^l,...,(i+1)*2^l - 1
+    levels: Vec<Vec<RangeUpdate>>,
+    /// The highest level of the tree, if capped
+    #[serde(default)]
+    max_level: Option<usize>,
+}
+
+impl RangeUpdates {
+    /// A segment tree whose largest aggregated update covers 2^max_level epochs.
+    /// Merging two nodes at level l takes 2^(l+1) multi-scalar multiplications
+    /// of size up to 2^l, so a cap bounds the cost of a deletion
+    /// at the price of more updates per catch-up
+    pub fn with_max_level(max_level: usize) -> Self {
+        Self {
+            levels: Vec::new(),
+            max_level: Some(max_level),
+        }
+    }
+
+    /// The number of deletions recorded
+    pub fn len(&self) -> usize {
+        self.levels.first().map_or(0, |l| l.len())
+    }
+
+    /// Whether no deletions have been recorded
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Records the deletion of `y`, which moved the accumulator
+    /// from `epoch` to `new_accumulator`, and merges every range it completes
+    pub fn push(&mut self, epoch: usize, y: UserID, new_accumulator: &Accumulator) {
+        if self.levels.is_empty() {
+            self.levels.push(Vec::new());
+        }
+        self.levels[0].push(RangeUpdate::new(epoch, y, new_accumulator));
+
+        let mut index = self.levels[0].len() - 1;
+        let mut level = 0;
+        // A right child completes its parent
+        while index % 2 == 1 && self.max_level.is_none_or(|max| level < max) {
+            let parent = self.levels[level][index - 1]
+                .merge(&self.levels[level][index])
+                .expect("siblings to be adjacent");
+            if self.levels.len() == level + 1 {
+                self.levels.push(Vec::new());
+            }
+            self.levels[level + 1].push(parent);
+            index /= 2;
+            level += 1;
+        }
+    }
+
+    /// Returns the fewest aggregated updates that move a witness
+    /// from `from_epoch` to `to_epoch`
+    pub fn get(&self, from_epoch: usize, to_epoch: usize) -> Option<Vec<RangeUpdate>> {
+        if to_epoch < from_epoch {
+            return None;
+        }
+        if to_epoch == from_epoch {
+            return Some(Vec::new());
+        }
+        let first_epoch = self.levels.first()?.first()?.epoch;
+        if from_epoch < first_epoch || to_epoch - first_epoch > self.len() {
+            return None;
+        }
+        let mut start = from_epoch - first_epoch;
+        let end = to_epoch - first_epoch;
+        let mut updates = Vec::new();
+        while start < end {
+            // Take the largest aligned range starting here that fits
+            let mut level = std::cmp::min(start.trailing_zeros() as usize, self.levels.len() - 1);
+            while start + (1 << level) > end {
+                level -= 1;
+            }
+            updates.push(self.levels[level][start >> level].clone());
+            start += 1 << level;
+        }
+        Some(updates)
+    }
+}
@@ -0,0 +1,57 @@
+use crate::accumulator::{Accumulator, PublicKey};
+use crate::utils::{AccParams, UserID};
+use blsful::inner_types::*;
+use serde::{Deserialize, Serialize};
+
+/// Public evidence that an epoch transition was the deletion of one element,
+/// i.e. V_{t+1} = V_t * 1/(y + alpha)
+#[derive(Copy, Clone, Debug, Deserialize, Serialize)]
+pub struct DeletionReceipt {
+    /// The epoch the deletion moved the accumulator to
+    pub epoch: usize,
+    /// The deleted element y
+    pub deleted: UserID,
+    /// The accumulator before the deletion V_t
+    pub previous: Accumulator,
+    /// The accumulator after the deletion V_{t+1}
+    pub accumulator: Accumulator,
+}
+
+impl DeletionReceipt {
+    /// Checks e(V_{t+1}, yP + Q) = e(V_t, P) against the witness public key Q
+    pub fn verify(&self, params: &AccParams, witness_key: &PublicKey) -> bool {
+        multi_miller_loop(&[
+            (
+                &self.accumulator.0.to_affine(),
+                &G2Prepared::from((params.get_p2() * self.deleted.0 + witness_key.0).to_affine()),
+            ),
+            (
+                &self.previous.0.to_affine(),
+                &G2Prepared::from(-params.get_p2().to_affine()),
+            ),
+        ])
+        .final_exponentiation()
+        .is_identity()
+        .into()
+    }
+
+    /// Checks that every receipt verifies and that each one
+    /// starts from the accumulator the one before it ended at
+    pub fn verify_chain(
+        receipts: &[DeletionReceipt],
+        params: &AccParams,
+        witness_key: &PublicKey,
+    ) -> Result<(), &'static str> {
+        if receipts.iter().any(|r| !r.verify(params, witness_key)) {
+            return Err("invalid deletion receipt");
+        }
+        if receipts
+            .windows(2)
+            .all(|w| w[1].epoch == w[0].epoch + 1 && w[1].previous == w[0].accumulator)
+        {
+            Ok(())
+        } else {
+            Err("broken receipt chain")
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use crate::accumulator::Accumulator;
+use crate::utils::{g1, AccParams, PublicKeys, UserID, SECURITY_BYTES};
+use crate::witness::{MembershipProof, Statement, Witness};
+use blsful::inner_types::*;
+use merlin::Transcript;
+use serde::{Deserialize, Serialize};
+
+/// A pseudonym H(scope)^{sk} output by a scoped membership proof.
+/// A user has one pseudonym per scope, so a relying party can tell
+/// when the same user proves membership twice in its scope
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+pub struct Pseudonym(pub G1Projective);
+
+impl Pseudonym {
+    /// The byte representation of the pseudonym
+    pub fn to_bytes(&self) -> [u8; 48] {
+        self.0.to_compressed()
+    }
+}
+
+/// A membership proof that also outputs the prover's pseudonym N = H(scope)^{sk},
+/// with T_3 = s_0H(scope) - cN proving it uses the secret key
+/// of the long-term signature in the membership proof
+#[derive(Copy, Clone, Debug, Deserialize, Serialize)]
+pub struct ScopedMembershipProof {
+    pub(crate) proof: MembershipProof,
+    pub(crate) pseudonym: Pseudonym,
+}
+
+impl ScopedMembershipProof {
+    /// The size of the proof in bytes
+    pub const BYTES: usize = MembershipProof::BYTES + 48;
+
+    /// The underlying membership proof
+    pub fn proof(&self) -> MembershipProof {
+        self.proof
+    }
+
+    /// The prover's pseudonym for the proof's scope
+    pub fn pseudonym(&self) -> Pseudonym {
+        self.pseudonym
+    }
+
+    /// Get the byte representation of the proof
+    pub fn to_bytes(&self) -> [u8; Self::BYTES] {
+        let mut res = [0u8; Self::BYTES];
+        res[..MembershipProof::BYTES].copy_from_slice(&self.proof.to_bytes());
+        res[MembershipProof::BYTES..].copy_from_slice(&self.pseudonym.to_bytes());
+        res
+    }
+
+    /// Convert a byte representation to a proof
+    pub fn from_bytes(input: &[u8; Self::BYTES]) -> Result<Self, &'static str> {
+        let proof: &[u8; MembershipProof::BYTES] = input[..MembershipProof::BYTES]
+            .try_into()
+            .map_err(|_| "invalid length")?;
+        Ok(Self {
+            proof: MembershipProof::from_bytes(proof)?,
+            pseudonym: Pseudonym(g1(&input[MembershipProof::BYTES..])?),
+        })
+    }
+
+    /// Verifies the proof for `scope` and the verifier's ephemeral challenge.
+    /// The pseudonym is then the prover's pseudonym for `scope`
+    pub fn verify(
+        &self,
+        params: &AccParams,
+        public_keys: &PublicKeys,
+        accumulator: &Accumulator,
+        scope: &[u8],
+        ephemeral_challenge: &[u8; 2 * SECURITY_BYTES],
+    ) -> bool {
+        if bool::from(self.pseudonym.0.is_identity()) {
+            return false;
+        }
+        // T_3 = s_0H(scope) - cN
+        let t_3 = scope_base(scope) * self.proof.s_0 - self.pseudonym.0 * self.proof.challenge;
+        Witness::check_bound(
+            &self.proof,
+            params,
+            public_keys,
+            accumulator,
+            |transcript| {
+                bind(
+                    transcript,
+                    scope,
+                    &self.pseudonym,
+                    &t_3,
+                    ephemeral_challenge,
+                )
+            },
+        )
+    }
+}
+
+impl Witness {
+    /// Constructs a membership proof that also outputs the user's pseudonym
+    /// H(scope)^{sk} for `scope`. The pseudonym is the same in every proof
+    /// for the same scope and unlinkable across scopes
+    pub fn make_scoped_membership_proof(
+        witness: &Witness,
+        user_id: &UserID,
+        statement: &Statement,
+        params: &AccParams,
+        scope: &[u8],
+        ephemeral_challenge: &[u8; 2 * SECURITY_BYTES],
+    ) -> Option<ScopedMembershipProof> {
+        let base = scope_base(scope);
+        let pseudonym = Pseudonym(base * witness.secret_key.0);
+        let proof = Self::prove_bound(witness, user_id, statement, params, |transcript, k| {
+            // T_3 = k_0H(scope)
+            bind(
+                transcript,
+                scope,
+                &pseudonym,
+                &(base * k[0]),
+                ephemeral_challenge,
+            )
+        })?;
+        Some(ScopedMembershipProof { proof, pseudonym })
+    }
+}
+
+// H(scope)
+fn scope_base(scope: &[u8]) -> G1Projective {
+    const DST: &[u8] = b"ALLOSAUR_PSEUDONYM_BLS12381G1_XMD:SHA-256_SSWU_RO_";
+    G1Projective::hash::<ExpandMsgXmd<sha2::Sha256>>(scope, DST)
+}
+
+// Adds the scope, the pseudonym and T_3 to the proof transcript
+fn bind(
+    transcript: &mut Transcript,
+    scope: &[u8],
+    pseudonym: &Pseudonym,
+    t_3: &G1Projective,
+    ephemeral_challenge: &[u8; 2 * SECURITY_BYTES],
+) {
+    transcript.append_message(b"Scope", scope);
+    transcript.append_message(b"N", &pseudonym.to_bytes());
+    transcript.append_message(b"T_3", &t_3.to_compressed());
+    transcript.append_message(b"Ephemeral challenge", ephemeral_challenge);
+}
@@ -1,11 +1,28 @@
 use crate::accumulator::{
-    Accumulator, Element, MembershipWitness, Polynomial, PublicKey, SecretKey,
+    generate_fr, Accumulator, Element, MembershipWitness, NonMembershipWitness, Polynomial,
+    PublicKey, SecretKey, SALT,
 };
-use crate::utils::{AccParams, PublicKeys, UserID};
+use crate::credential::{attribute_message, Credential};
+use crate::epoch::{
+    EpochHead, EpochKeyShare, EpochPublicKey, EpochPublicKeyShare, EpochSecretKey,
+    EpochSignatureShare, SignedEpochHead,
+};
+use crate::log::{SignedLogCheckpoint, TransparencyLog};
+use crate::range_update::{RangeUpdate, RangeUpdates};
+use crate::receipt::DeletionReceipt;
+use crate::transport::{EncryptionPublicKey, SealedRequest, SealedResponse};
+use crate::utils::{g1, sc, usize_from, AccParams, PublicKeys, UserID};
 use blsful::inner_types::*;
 use merlin::Transcript;
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// How many seconds a proposed head's timestamp may be ahead of the server's clock
+const MAX_CLOCK_SKEW: u64 = 60;
+
+/// How many epochs a credential stays valid unless the server sets its own lifetime
+pub const CREDENTIAL_LIFETIME: usize = 1 << 16;
 
 /// An ALLOSAUR server
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -24,6 +41,30 @@ pub struct Server {
     pub all_witnesses: HashMap<UserID, MembershipWitness>,
     /// The list of deletions y_1,...,y_d
     pub deletions: Vec<UserID>,
+    /// The aggregated updates for ranges of epochs.
+    /// Servers saved without them rebuild them with `restore`
+    #[serde(default)]
+    pub range_updates: RangeUpdates,
+    /// The BLS key the server signs accumulators with
+    pub epoch_secret_key: EpochSecretKey,
+    /// The key users seal their update requests to
+    pub encryption_secret_key: SecretKey,
+    /// The signed head of every epoch.
+    /// Servers saved without heads sign them again with `restore`
+    #[serde(default)]
+    pub heads: Vec<SignedEpochHead>,
+    /// The server's share of the joint key that certifies epoch heads
+    pub epoch_key_share: Option<EpochKeyShare>,
+    /// The heads certified under the joint key, from the first epoch on
+    #[serde(default)]
+    pub certified: Vec<SignedEpochHead>,
+    /// The append-only log of epoch heads.
+    /// Servers saved without it append the heads again with `restore`
+    #[serde(default)]
+    pub log: TransparencyLog,
+    /// How many epochs the credentials the server issues stay valid
+    #[serde(default = "default_credential_lifetime")]
+    pub credential_lifetime: usize,
 }
 
 impl Server {
@@ -33,9 +74,16 @@ impl Server {
         let s_m = SecretKey::new(None);
         let q = params.get_p2() * alpha.0;
         let q_m = params.get_k2() * s_m.0;
-        let v = params.get_p1() * SecretKey::new(None).0;
+        let v = Accumulator(params.get_p1() * SecretKey::new(None).0);
+        let epoch_secret_key = EpochSecretKey::new();
+        let encryption_secret_key = SecretKey::new(None);
+        let head = EpochHead::new(1, v, None)
+            .sign(&epoch_secret_key)
+            .expect("a valid key");
+        let mut log = TransparencyLog::default();
+        log.append(&head.head).expect("the first epoch");
         Server {
-            accumulators: vec![Accumulator(v)],
+            accumulators: vec![v],
             witness_secret_key: alpha,
             sign_secret_key: s_m,
             public_keys: PublicKeys {
@@ -45,7 +93,41 @@ impl Server {
             all_users: HashSet::new(),
             all_witnesses: HashMap::new(),
             deletions: Vec::new(),
+            range_updates: RangeUpdates::default(),
+            epoch_secret_key,
+            encryption_secret_key,
+            heads: vec![head],
+            epoch_key_share: None,
+            certified: Vec::new(),
+            log,
+            credential_lifetime: CREDENTIAL_LIFETIME,
+        }
+    }
+
+    /// Rebuilds the range updates, heads and log entries that are missing,
+    /// e.g., for a server saved before they existed.
+    /// Everything is derived from the accumulators, deletions and the server's own keys,
+    /// which a saved server must always have
+    pub fn restore(&mut self) -> Result<(), &'static str> {
+        if self.range_updates.is_empty() {
+            for (i, y) in self.deletions.iter().enumerate() {
+                self.range_updates
+                    .push(i + 1, *y, &self.accumulators[i + 1]);
+            }
+        }
+        for epoch in self.heads.len() + 1..=self.get_epoch() {
+            let head = EpochHead::new(
+                epoch,
+                self.accumulators[epoch - 1],
+                self.heads.last().map(|h| &h.head),
+            )
+            .sign(&self.epoch_secret_key)?;
+            self.heads.push(head);
         }
+        for head in self.heads.iter().skip(self.log.len()) {
+            self.log.append(&head.head)?;
+        }
+        Ok(())
     }
 
     /// "Adds" a new element by create a witness for it and inserting it into the internal list
@@ -79,7 +161,7 @@ impl Server {
             None => None,
             Some(wit) => {
                 let new_accumulator = Accumulator(wit.0);
-                self.accumulators.push(new_accumulator);
+                self.push_accumulator(user_id, new_accumulator);
 
                 // Update all witnesses for the new accumulator
                 for (other_y, other_witness) in self.all_witnesses.iter_mut() {
@@ -108,16 +190,32 @@ impl Server {
                     .invert()
                     .expect("to not be zero"),
         );
-        self.accumulators.push(new_accumulator);
+        self.push_accumulator(y, new_accumulator);
         // Update all witnesses for the new accumulator
 
         self.deletions.push(y);
         Some(new_accumulator)
     }
 
+    // Moves to the next epoch after deleting y
+    fn push_accumulator(&mut self, y: UserID, accumulator: Accumulator) {
+        self.range_updates.push(self.get_epoch(), y, &accumulator);
+        self.accumulators.push(accumulator);
+        let head = EpochHead::new(
+            self.get_epoch(),
+            accumulator,
+            self.heads.last().map(|h| &h.head),
+        )
+        .sign(&self.epoch_secret_key)
+        .expect("a valid key");
+        self.log.append(&head.head).expect("the next epoch");
+        self.heads.push(head);
+    }
+
     /// Given a user ID y and a signature proof (via challenge and response),
-    /// returns (C,R) such that C is a witness for y and R is a long-term
-    /// signature
+    /// returns (C,R,A) such that C is a witness for y, R is a long-term
+    /// signature and A is a credential without attributes that expires
+    /// `credential_lifetime` epochs from now
     pub fn witness(
         &self,
         params: &AccParams,
@@ -125,12 +223,135 @@ impl Server {
         challenge: &Element,
         response: &Element,
         user_pub_key: &G1Projective,
-    ) -> Option<(MembershipWitness, G1Projective)> {
+    ) -> Option<(MembershipWitness, G1Projective, Credential)> {
         // Only issue a full witness once a user is added
         if !self.all_witnesses.contains_key(y) {
             return None;
         }
-        // Check quick Schnoor proof that user knows a secret key for this public key
+        let signature = self.sign_user(params, y, challenge, response, user_pub_key)?;
+        let credential = self.sign_credential(params, y, user_pub_key, &[])?;
+        // Look up witness (could compute as needed, but lookup is better for MPC version)
+        let acc_witness = self.all_witnesses[y];
+        Some((acc_witness, signature, credential))
+    }
+
+    /// Given a user ID y, a signature proof (via challenge and response)
+    /// and attributes m_1,..m_n, returns a BBS+ signature
+    /// A = (K0 + xK + sH_0 + yH_y + tH_e + sum m_iH_i)/(q_m + e) over all of them,
+    /// with e and s fresh for every signature.
+    /// The server decides the expiry epoch t as `credential_lifetime` epochs from now.
+    /// The user keeps it in a `Credential` next to their witness
+    pub fn credential(
+        &self,
+        params: &AccParams,
+        y: &UserID,
+        challenge: &Element,
+        response: &Element,
+        user_pub_key: &G1Projective,
+        attributes: &[Element],
+    ) -> Option<Credential> {
+        // Only sign for users that were added
+        if !self.all_witnesses.contains_key(y) {
+            return None;
+        }
+        if !Self::check_key_proof(params, challenge, response, user_pub_key) {
+            return None;
+        }
+        self.sign_credential(params, y, user_pub_key, attributes)
+    }
+
+    // Signs a credential for a user whose key proof was already checked
+    fn sign_credential(
+        &self,
+        params: &AccParams,
+        y: &UserID,
+        user_pub_key: &G1Projective,
+        attributes: &[Element],
+    ) -> Option<Credential> {
+        let expiry = self.get_epoch().checked_add(self.credential_lifetime)? as u64;
+        let rng = rand::rngs::OsRng;
+        let e = generate_fr(SALT, None, rng);
+        let s = generate_fr(SALT, None, rng);
+        let message = user_pub_key
+            + params.get_k0()
+            + params.get_h0() * s
+            + params.get_hy() * y.0
+            + params.get_he() * Scalar::from(expiry)
+            + attribute_message(params, attributes);
+        let signature = message * Option::<Scalar>::from((self.sign_secret_key.0 + e).invert())?;
+        Some(Credential {
+            attributes: attributes.to_vec(),
+            expiry,
+            signature,
+            e,
+            s,
+        })
+    }
+
+    /// Given a user ID y and a signature proof (via challenge and response),
+    /// returns ((C,d),R) such that (C,d) is a non-membership witness for y
+    /// against the deletions so far and R is a long-term signature.
+    /// With f(x) the product of (y' + x) over deleted y',
+    /// d = f(-y) and C = V_t * (f(alpha) - d)/(y + alpha)
+    pub fn non_membership_witness(
+        &self,
+        params: &AccParams,
+        y: &UserID,
+        challenge: &Element,
+        response: &Element,
+        user_pub_key: &G1Projective,
+    ) -> Option<(NonMembershipWitness, G1Projective)> {
+        // Only added users that were not deleted
+        if !self.all_witnesses.contains_key(y) {
+            return None;
+        }
+        let signature = self.sign_user(params, y, challenge, response, user_pub_key)?;
+        let (f_alpha, d) =
+            self.deletions
+                .iter()
+                .fold((Scalar::ONE, Scalar::ONE), |(f_alpha, d), deleted| {
+                    (
+                        f_alpha * (deleted.0 + self.witness_secret_key.0),
+                        d * (deleted.0 - y.0),
+                    )
+                });
+        let c = self.get_accumulator().0
+            * ((f_alpha - d)
+                * (y.0 + self.witness_secret_key.0)
+                    .invert()
+                    .expect("to not be zero"));
+        Some((NonMembershipWitness { c, d }, signature))
+    }
+
+    // Checks the user's Schnorr proof for their public key
+    // and signs y and (user_pub_key + K0)
+    fn sign_user(
+        &self,
+        params: &AccParams,
+        y: &UserID,
+        challenge: &Element,
+        response: &Element,
+        user_pub_key: &G1Projective,
+    ) -> Option<G1Projective> {
+        if !Self::check_key_proof(params, challenge, response, user_pub_key) {
+            return None;
+        }
+        // Sign y and (user_pub_key + K0) using the signing secret key
+        Some(
+            (user_pub_key + params.get_k0())
+                * ((y.0 + self.sign_secret_key.0)
+                    .invert()
+                    .expect("to not be zero")),
+        )
+    }
+
+    // Checks a quick Schnorr proof that the user knows a secret key for this public key
+    fn check_key_proof(
+        params: &AccParams,
+        challenge: &Element,
+        response: &Element,
+        user_pub_key: &G1Projective,
+    ) -> bool {
         let mut transcript = Transcript::new(b"user_signature_proof");
         transcript.append_message(b"user_pub_key", user_pub_key.to_bytes().as_ref());
         transcript.append_message(
@@ -140,41 +361,34 @@ impl Server {
                 .as_ref(),
         );
         let check = Element::from_transcript(b"challenge", &mut transcript);
-        if check != *challenge {
-            return None;
-        }
-        // Look up witness (could compute as needed, but lookup is better for MPC version)
-        let acc_witness = self.all_witnesses[y];
-        // Sign y and (user_pub_key + K0) using the signing secret key
-        let signature = (user_pub_key + params.get_k0())
-            * ((y.0 + self.sign_secret_key.0)
-                .invert()
-                .expect("to not be zero"));
-        Some((acc_witness, signature))
+        check == *challenge
     }
 
     /// Given shares from a user, returns the array of (d,W) which can each be used as
     /// C <- (C - W)*(1/d)
-    /// for an update
-    pub fn update(
-        &self,
-        num_epochs: usize,
-        y_shares: &[Scalar],
-    ) -> (Vec<Scalar>, Vec<G1Projective>) {
-        // If user requests more updates than possible
-        if num_epochs > self.deletions.len() {
-            return (Vec::new(), Vec::new());
+    /// for an update from epoch `epoch - num_epochs` to `epoch`.
+    /// If the server cannot serve this, the response is empty
+    /// and carries the server's latest epoch instead
+    pub fn update(&self, epoch: usize, num_epochs: usize, y_shares: &[Scalar]) -> ServerUpdate {
+        // If user requests an epoch the server does not have, or more updates than possible
+        if epoch > self.get_epoch() || epoch == 0 || num_epochs > epoch - 1 {
+            return ServerUpdate {
+                epoch: self.get_epoch(),
+                ds: Vec::new(),
+                vs: Vec::new(),
+            };
         }
 
         // Degree of user shares
         let k = y_shares.len() + 1;
 
         // The arrays to return
-        let mut ds = Vec::with_capacity(self.deletions.len());
-        let mut vs = Vec::with_capacity(self.deletions.len());
+        let mut ds = Vec::with_capacity(num_epochs);
+        let mut vs = Vec::with_capacity(num_epochs);
 
-        let n_del = self.deletions.len();
-        let n_acc = self.accumulators.len();
+        // Only use the deletions up to the requested epoch
+        let n_del = epoch - 1;
+        let n_acc = epoch;
 
         // Index of updates to build arrays
         let mut del_start = n_del - num_epochs;
@@ -219,7 +433,24 @@ impl Server {
             del_start += k - 1;
             acc_start += k - 1;
         }
-        (ds, vs)
+        ServerUpdate { epoch, ds, vs }
+    }
+
+    /// Answers an update request sealed to this server,
+    /// sealing the response back to the user
+    pub fn sealed_update(&self, request: &SealedRequest) -> Result<SealedResponse, &'static str> {
+        let (request, response_key) = request.open(&self.encryption_secret_key)?;
+        let response = self.update(request.epoch, request.epoch_diff, &request.y_shares);
+        Ok(response_key.seal(&response))
+    }
+
+    /// Returns the aggregated updates that move a witness from `from_epoch`
+    /// to `to_epoch`, using O(log(to_epoch - from_epoch)) updates
+    pub fn range_update(&self, from_epoch: usize, to_epoch: usize) -> Option<Vec<RangeUpdate>> {
+        if to_epoch > self.get_epoch() {
+            return None;
+        }
+        self.range_updates.get(from_epoch, to_epoch)
     }
 
     /// The latest epoch of the accumulator
@@ -227,11 +458,138 @@ impl Server {
         self.accumulators.len()
     }
 
-    /// Get the most recent accumulator
+    /// Get the most recent accumulator.
+    /// Others should get it from the signed head instead
     pub fn get_accumulator(&self) -> Accumulator {
         *(self.accumulators.last().unwrap())
     }
 
+    /// Get the accumulator at a given epoch
+    pub fn get_accumulator_at(&self, epoch: usize) -> Option<Accumulator> {
+        epoch
+            .checked_sub(1)
+            .and_then(|i| self.accumulators.get(i))
+            .copied()
+    }
+
+    /// Get the receipt for the deletion that moved the accumulator to `epoch`
+    pub fn deletion_receipt(&self, epoch: usize) -> Option<DeletionReceipt> {
+        let i = epoch.checked_sub(2)?;
+        Some(DeletionReceipt {
+            epoch,
+            deleted: *self.deletions.get(i)?,
+            previous: *self.accumulators.get(i)?,
+            accumulator: *self.accumulators.get(i + 1)?,
+        })
+    }
+
+    /// Get the receipts for every deletion so far
+    pub fn deletion_receipts(&self) -> Vec<DeletionReceipt> {
+        (2..=self.get_epoch())
+            .filter_map(|epoch| self.deletion_receipt(epoch))
+            .collect()
+    }
+
+    /// Get the signed head of a given epoch
+    pub fn get_epoch_head(&self, epoch: usize) -> Option<SignedEpochHead> {
+        epoch
+            .checked_sub(1)
+            .and_then(|i| self.heads.get(i))
+            .copied()
+    }
+
+    /// Get the signed head of the latest epoch
+    pub fn get_head(&self) -> SignedEpochHead {
+        *(self.heads.last().unwrap())
+    }
+
+    /// Get the log checkpoint of the latest epoch, signed with the epoch key
+    pub fn get_log_checkpoint(&self) -> SignedLogCheckpoint {
+        self.log
+            .checkpoint()
+            .sign(&self.epoch_secret_key)
+            .expect("a valid key")
+    }
+
+    /// Get the signed log checkpoint from when the log had `size` epochs
+    pub fn get_log_checkpoint_at(&self, size: usize) -> Option<SignedLogCheckpoint> {
+        self.log
+            .checkpoint_at(size)?
+            .sign(&self.epoch_secret_key)
+            .ok()
+    }
+
+    /// Partially signs a proposed head with the server's share of the joint key,
+    /// if the head matches the server's accumulator for that epoch
+    /// and directly follows the latest head the server saw certified
+    pub fn sign_head_share(&self, head: &EpochHead) -> Result<EpochSignatureShare, &'static str> {
+        let key_share = self.epoch_key_share.as_ref().ok_or("no epoch key share")?;
+        if self.get_accumulator_at(head.epoch) != Some(head.accumulator) {
+            return Err("head does not match accumulator");
+        }
+        self.check_next_certified(head)?;
+        head.sign_share(key_share)
+    }
+
+    /// Stores a certificate for the next epoch after checking it
+    /// like `sign_head_share` and against the joint key
+    pub fn store_certificate(
+        &mut self,
+        certificate: &SignedEpochHead,
+        joint_key: &EpochPublicKey,
+    ) -> Result<(), &'static str> {
+        let accumulator = certificate.accumulator(joint_key)?;
+        if self.get_accumulator_at(certificate.head.epoch) != Some(accumulator) {
+            return Err("head does not match accumulator");
+        }
+        self.check_next_certified(&certificate.head)?;
+        self.certified.push(*certificate);
+        Ok(())
+    }
+
+    /// Get the certificate for `epoch`
+    pub fn get_certificate(&self, epoch: usize) -> Option<SignedEpochHead> {
+        epoch
+            .checked_sub(1)
+            .and_then(|i| self.certified.get(i))
+            .copied()
+    }
+
+    // Checks that `head` extends the certified chain by one epoch,
+    // with a timestamp that is neither before the previous head nor in the future
+    fn check_next_certified(&self, head: &EpochHead) -> Result<(), &'static str> {
+        let follows = match self.certified.last() {
+            Some(last) => head.follows(&last.head),
+            None => head.epoch == 1 && head.previous == [0u8; 32],
+        };
+        if !follows {
+            return Err("head does not follow the certified head");
+        }
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        if head.timestamp > now + MAX_CLOCK_SKEW {
+            return Err("head is from the future");
+        }
+        Ok(())
+    }
+
+    /// Get the public key of the server's share of the joint key
+    pub fn get_epoch_public_key_share(&self) -> Option<EpochPublicKeyShare> {
+        self.epoch_key_share.as_ref()?.public_key().ok()
+    }
+
+    /// Get the public key for the server's signed accumulators
+    pub fn get_epoch_public_key(&self) -> EpochPublicKey {
+        self.epoch_secret_key.public_key()
+    }
+
+    /// Get the key users seal update requests to this server with
+    pub fn get_encryption_public_key(&self) -> EncryptionPublicKey {
+        EncryptionPublicKey(G1Projective::GENERATOR * self.encryption_secret_key.0)
+    }
+
     /// Get the witness public key
     pub fn get_witness_public_key(&self) -> PublicKey {
         self.public_keys.witness_key
@@ -242,8 +600,58 @@ impl Server {
         self.public_keys.sign_key
     }
 
-    /// Get the secret key for the witness
+    /// Get the public keys
     pub fn get_public_keys(&self) -> PublicKeys {
         self.public_keys
     }
 }
+
+/// A server's response to a user update message
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ServerUpdate {
+    /// The epoch the update brings the witness to
+    pub epoch: usize,
+    /// The shares of the chunks of the d polynomial
+    pub ds: Vec<Scalar>,
+    /// The shares of the chunks of the v polynomial
+    pub vs: Vec<G1Projective>,
+}
+
+impl ServerUpdate {
+    /// Get the byte representation of the update
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut res = Vec::with_capacity(16 + 80 * self.ds.len());
+        res.extend_from_slice(&(self.epoch as u64).to_be_bytes());
+        res.extend_from_slice(&(self.ds.len() as u64).to_be_bytes());
+        for d in &self.ds {
+            res.extend_from_slice(&d.to_be_bytes());
+        }
+        for v in &self.vs {
+            res.extend_from_slice(&v.to_compressed());
+        }
+        res
+    }
+
+    /// Convert a byte representation to an update
+    pub fn from_bytes(input: &[u8]) -> Result<Self, &'static str> {
+        if input.len() < 16 {
+            return Err("invalid update length");
+        }
+        let epoch = usize_from(&input[0..8])?;
+        let count = usize_from(&input[8..16])?;
+        let input = &input[16..];
+        if count.checked_mul(80) != Some(input.len()) {
+            return Err("invalid update length");
+        }
+        let (ds, vs) = input.split_at(32 * count);
+        Ok(Self {
+            epoch,
+            ds: ds.chunks(32).map(sc).collect::<Result<_, _>>()?,
+            vs: vs.chunks(48).map(g1).collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+fn default_credential_lifetime() -> usize {
+    CREDENTIAL_LIFETIME
+}
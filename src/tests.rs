@@ -5,6 +5,49 @@ use crate::{utils::*, *};
 use blsful::inner_types::*;
 use rand::RngCore;
 
+// Epoch keys for `n` replicas of `server`, the first being the server's own
+fn replica_keys(server: &Server, n: usize) -> Vec<EpochSecretKey> {
+    let mut keys = vec![server.epoch_secret_key.clone()];
+    keys.extend((1..n).map(|_| EpochSecretKey::new()));
+    keys
+}
+
+// The public epoch keys users pin for `keys`
+fn pin(keys: &[EpochSecretKey]) -> Vec<EpochPublicKey> {
+    keys.iter().map(|k| k.public_key()).collect()
+}
+
+// Clones `server` with its heads signed under `key` instead
+fn replica(server: &Server, key: &EpochSecretKey) -> Server {
+    let mut replica = server.clone();
+    replica.heads = server
+        .heads
+        .iter()
+        .map(|h| h.head.sign(key).unwrap())
+        .collect();
+    replica.epoch_secret_key = key.clone();
+    replica
+}
+
+// Clones `server` once per key, see `replica`
+fn replicas(server: &Server, keys: &[EpochSecretKey]) -> Vec<Server> {
+    keys.iter().map(|key| replica(server, key)).collect()
+}
+
+// A new server with `n` added users that each hold a witness
+fn setup(params: &AccParams, n: usize) -> (Server, Vec<User>) {
+    let mut server = Server::new(params);
+    let mut users = Vec::new();
+    for _ in 0..n {
+        let mut user =
+            User::new(&server, UserID::random(), &[server.get_epoch_public_key()]).unwrap();
+        server.add(user.get_id());
+        user.create_witness(params, &server);
+        users.push(user);
+    }
+    (server, users)
+}
+
 // Generates a new accumulator and adds elements
 #[test]
 fn new_accmulator_100() {
@@ -86,7 +129,7 @@ fn test_witness_issue() {
     let mut server = Server::new(&params);
     let mut users = Vec::new();
     for _ in 0..10 {
-        users.push(User::new(&server, UserID::random()));
+        users.push(User::new(&server, UserID::random(), &[server.get_epoch_public_key()]).unwrap());
         server.add(users.last().unwrap().get_id());
         users.last_mut().unwrap().create_witness(&params, &server);
         assert!(users
@@ -105,9 +148,11 @@ fn test_witness_update() {
     const USERS: usize = 10;
     let params = AccParams::default();
     let mut server = Server::new(&params);
+    let keys = replica_keys(&server, SERVERS);
+    let pinned = pin(&keys);
     let mut users = Vec::new();
     for _ in 0..USERS {
-        users.push(User::new(&server, UserID::random()));
+        users.push(User::new(&server, UserID::random(), &pinned).unwrap());
         server.add(users.last().unwrap().get_id());
         users.last_mut().unwrap().create_witness(&params, &server);
         assert!(users
@@ -116,14 +161,14 @@ fn test_witness_update() {
             .check_witness(&params, &server.get_accumulator())
             .is_ok());
     }
-    for i in 1..USERS {
-        server.delete(users[i].get_id());
-        assert!(!users[i]
+    for user in &users[1..USERS] {
+        server.delete(user.get_id());
+        assert!(user
             .check_witness(&params, &server.get_accumulator())
-            .is_ok());
+            .is_err());
     }
-    let servers: Vec<Server> = (0..SERVERS).map(|_| server.clone()).collect();
-    let res = users[0].update(&servers, SERVER_THRESHOLD);
+    let servers = replicas(&server, &keys);
+    let res = users[0].update(&params, &servers, SERVER_THRESHOLD);
     assert!(res.is_ok());
     assert!(users[0]
         .check_witness(&params, &server.get_accumulator())
@@ -138,9 +183,11 @@ fn test_witness_update_add() {
     const USERS: usize = 10;
     let params = AccParams::default();
     let mut server = Server::new(&params);
+    let keys = replica_keys(&server, SERVERS);
+    let pinned = pin(&keys);
     let mut users = Vec::new();
     for _ in 0..USERS {
-        users.push(User::new(&server, UserID::random()));
+        users.push(User::new(&server, UserID::random(), &pinned).unwrap());
         server.add(users.last().unwrap().get_id());
         users.last_mut().unwrap().create_witness(&params, &server);
         assert!(users
@@ -149,19 +196,19 @@ fn test_witness_update_add() {
             .check_witness(&params, &server.get_accumulator())
             .is_ok());
     }
-    for i in 1..USERS {
-        server.delete(users[i].get_id());
-        assert!(!users[i]
+    for user in &users[1..USERS] {
+        server.delete(user.get_id());
+        assert!(user
             .check_witness(&params, &server.get_accumulator())
-            .is_ok());
+            .is_err());
     }
     for i in 1..USERS {
         server.delete(users[i].get_id());
-        users.push(User::new(&server, UserID::random()));
+        users.push(User::new(&server, UserID::random(), &pinned).unwrap());
         server.add(users.last().unwrap().get_id());
     }
-    let servers: Vec<Server> = (0..SERVERS).map(|_| server.clone()).collect();
-    let res = users[0].update(&servers, SERVER_THRESHOLD);
+    let servers = replicas(&server, &keys);
+    let res = users[0].update(&params, &servers, SERVER_THRESHOLD);
     assert!(res.is_ok());
     assert!(users[0]
         .check_witness(&params, &server.get_accumulator())
@@ -175,14 +222,14 @@ fn basic_membership_proof() {
     let mut server = Server::new(&params);
     let mut users = Vec::new();
     for _ in 0..10 {
-        users.push(User::new(&server, UserID::random()));
+        users.push(User::new(&server, UserID::random(), &[server.get_epoch_public_key()]).unwrap());
         server.add(users.last().unwrap().get_id());
         users.last_mut().unwrap().create_witness(&params, &server);
     }
-    for i in 0..10 {
+    for user in &users {
         let mut ephemeral_challenge = [0u8; 2 * SECURITY_BYTES];
         rand::rngs::OsRng.fill_bytes(&mut ephemeral_challenge);
-        let proof = users[i]
+        let proof = user
             .make_membership_proof(&params, &server.get_public_keys(), &ephemeral_challenge)
             .unwrap();
 
@@ -205,17 +252,17 @@ fn basic_membership_proof_failure() {
     let mut server = Server::new(&params);
     let mut users = Vec::new();
     for _ in 0..SERVERS {
-        users.push(User::new(&server, UserID::random()));
+        users.push(User::new(&server, UserID::random(), &[server.get_epoch_public_key()]).unwrap());
         server.add(users.last().unwrap().get_id());
         users.last_mut().unwrap().create_witness(&params, &server);
     }
-    for i in 1..SERVERS {
-        server.delete(users[i].get_id());
+    for user in &users[1..SERVERS] {
+        server.delete(user.get_id());
     }
-    for i in 0..SERVERS {
+    for user in &users {
         let mut ephemeral_challenge = [0u8; 2 * SECURITY_BYTES];
         rand::rngs::OsRng.fill_bytes(&mut ephemeral_challenge);
-        let proof = users[i]
+        let proof = user
             .make_membership_proof(&params, &server.get_public_keys(), &ephemeral_challenge)
             .unwrap();
 
@@ -239,7 +286,7 @@ fn test_split_witness_update() {
     let mut server = Server::new(&params);
     let mut users = Vec::new();
     for _ in 0..USERS {
-        users.push(User::new(&server, UserID::random()));
+        users.push(User::new(&server, UserID::random(), &[server.get_epoch_public_key()]).unwrap());
         server.add(users.last().unwrap().get_id());
         users.last_mut().unwrap().create_witness(&params, &server);
         assert!(users
@@ -248,11 +295,11 @@ fn test_split_witness_update() {
             .check_witness(&params, &server.get_accumulator())
             .is_ok());
     }
-    for i in 1..USERS {
-        server.delete(users[i].get_id());
-        assert!(!users[i]
+    for user in &users[1..USERS] {
+        server.delete(user.get_id());
+        assert!(user
             .check_witness(&params, &server.get_accumulator())
-            .is_ok());
+            .is_err());
     }
     let servers: Vec<Server> = (0..SERVERS).map(|_| server.clone()).collect();
     let res = users[0].prepare_for_update(servers[0].get_epoch(), SERVERS, SERVER_THRESHOLD);
@@ -262,8 +309,8 @@ fn test_split_witness_update() {
         y_shares,
         y_values,
     } = res.unwrap();
-    let dvs: Vec<(Vec<Scalar>, Vec<G1Projective>)> = (0..SERVERS)
-        .map(|i| servers[i].update(d, &y_shares[i]))
+    let dvs: Vec<ServerUpdate> = (0..SERVERS)
+        .map(|i| servers[i].update(servers[i].get_epoch(), d, &y_shares[i]))
         .collect();
     let res = users[0].post_update(
         users[0].witness.as_ref().unwrap().witness,
@@ -273,7 +320,9 @@ fn test_split_witness_update() {
         &dvs,
     );
     assert!(res.is_ok());
-    users[0].witness.as_mut().map(|w| w.witness = res.unwrap());
+    if let Some(w) = users[0].witness.as_mut() {
+        w.witness = res.unwrap();
+    }
     assert!(users[0]
         .check_witness(&params, &server.get_accumulator())
         .is_ok());
@@ -290,7 +339,7 @@ fn test_witness_split_update_add() {
     let mut server = Server::new(&params);
     let mut users = Vec::new();
     for _ in 0..USERS {
-        users.push(User::new(&server, UserID::random()));
+        users.push(User::new(&server, UserID::random(), &[server.get_epoch_public_key()]).unwrap());
         server.add(users.last().unwrap().get_id());
         users.last_mut().unwrap().create_witness(&params, &server);
         assert!(users
@@ -299,15 +348,15 @@ fn test_witness_split_update_add() {
             .check_witness(&params, &server.get_accumulator())
             .is_ok());
     }
-    for i in 1..USERS {
-        server.delete(users[i].get_id());
-        assert!(!users[i]
+    for user in &users[1..USERS] {
+        server.delete(user.get_id());
+        assert!(user
             .check_witness(&params, &server.get_accumulator())
-            .is_ok());
+            .is_err());
     }
     for i in 1..USERS {
         server.delete(users[i].get_id());
-        users.push(User::new(&server, UserID::random()));
+        users.push(User::new(&server, UserID::random(), &[server.get_epoch_public_key()]).unwrap());
         server.add(users.last().unwrap().get_id());
     }
     let servers: Vec<Server> = (0..SERVERS).map(|_| server.clone()).collect();
@@ -318,8 +367,8 @@ fn test_witness_split_update_add() {
         y_shares,
         y_values,
     } = res.unwrap();
-    let dvs: Vec<(Vec<Scalar>, Vec<G1Projective>)> = (0..SERVERS)
-        .map(|i| servers[i].update(d, &y_shares[i]))
+    let dvs: Vec<ServerUpdate> = (0..SERVERS)
+        .map(|i| servers[i].update(servers[i].get_epoch(), d, &y_shares[i]))
         .collect();
 
     let res = users[0].post_update(
@@ -330,7 +379,9 @@ fn test_witness_split_update_add() {
         &dvs,
     );
     assert!(res.is_ok());
-    users[0].witness.as_mut().map(|w| w.witness = res.unwrap());
+    if let Some(w) = users[0].witness.as_mut() {
+        w.witness = res.unwrap();
+    }
     assert!(users[0]
         .check_witness(&params, &server.get_accumulator())
         .is_ok());
@@ -350,7 +401,7 @@ fn single_server_split_batch_update() {
     let items: Vec<Element> = (0..USERS).map(|_| Element::random()).collect();
     let mut acc = Accumulator::with_elements(&key, items.as_slice());
 
-    let y = items.last().unwrap().clone();
+    let y = *items.last().unwrap();
     let mut witness = MembershipWitness::new(y, acc, &key).unwrap();
 
     let additions: Vec<Element> = (0..ADDITIONS).map(|_| Element::random()).collect();
@@ -371,7 +422,1731 @@ fn single_server_split_batch_update() {
         deltas[i] = acc.update_assign(&key, addition, split_deletions[i]);
     }
     for (i, addition) in split_additions.iter().enumerate() {
-        witness.batch_update_assign(y, &addition, &split_deletions[i], &deltas[i]);
+        witness.batch_update_assign(y, addition, split_deletions[i], &deltas[i]);
     }
     assert!(witness.verify(y, pk, acc));
 }
+
+// Tests that aggregated range updates match a direct batch update
+// and let a user catch up with few packets
+#[test]
+fn test_range_update() {
+    const USERS: usize = 50;
+    let params = AccParams::default();
+    let (mut server, mut users) = setup(&params, USERS);
+    for user in &users[1..USERS] {
+        server.quick_delete(user.get_id());
+    }
+    // Epochs 1..33 are covered by a single aggregated update
+    let updates = server.range_update(1, 33).unwrap();
+    assert_eq!(updates.len(), 1);
+    let mut acc = server.accumulators[0];
+    let coefficients = acc.update_assign(&server.witness_secret_key, &[], &server.deletions[..32]);
+    assert_eq!(updates[0].coefficients, coefficients);
+
+    // 3..50 = 3..5 + 5..9 + 9..17 + 17..33 + 33..49 + 49..50
+    let updates = server.range_update(3, server.get_epoch()).unwrap();
+    assert_eq!(updates.len(), 6);
+
+    // Capping the levels at 2^2 epochs splits 1..33 into 8 updates
+    let mut capped = RangeUpdates::with_max_level(2);
+    for (i, y) in server.deletions.iter().enumerate() {
+        capped.push(i + 1, *y, &server.accumulators[i + 1]);
+    }
+    assert_eq!(capped.get(1, 33).unwrap().len(), 8);
+    assert_eq!(capped.get(3, server.get_epoch()).unwrap().len(), 13);
+
+    // Updates that do not lead to the signed accumulator are rejected
+    let mut tampered = server.clone();
+    tampered.range_updates = RangeUpdates::default();
+    for (i, y) in server.deletions.iter().enumerate() {
+        tampered
+            .range_updates
+            .push(i + 1, *y, &server.accumulators[i]);
+    }
+    let mut user = users[0].clone();
+    assert!(user.range_update(&params, &tampered).is_err());
+    assert_eq!(user.epoch, users[0].epoch);
+
+    assert!(users[1].clone().range_update(&params, &server).is_err());
+    assert!(users[0].range_update(&params, &server).is_ok());
+    assert!(users[0]
+        .check_witness(&params, &server.get_accumulator())
+        .is_ok());
+    assert_eq!(users[0].epoch, server.get_epoch());
+}
+
+// Tests that a user updates to the highest epoch a threshold of servers
+// agree on when the servers are at different epochs
+#[test]
+fn test_witness_update_mixed_epochs() {
+    const SERVER_THRESHOLD: usize = 3;
+    const USERS: usize = 10;
+    let params = AccParams::default();
+    let mut server = Server::new(&params);
+    let keys = replica_keys(&server, 5);
+    let pinned = pin(&keys);
+    let mut users = Vec::new();
+    for _ in 0..USERS {
+        users.push(User::new(&server, UserID::random(), &pinned).unwrap());
+        server.add(users.last().unwrap().get_id());
+        users.last_mut().unwrap().create_witness(&params, &server);
+    }
+    for user in &users[2..USERS - 1] {
+        server.delete(user.get_id());
+    }
+    let lagging = server.clone();
+    server.delete(users[USERS - 1].get_id());
+    let mut ahead = server.clone();
+    ahead.delete(users[1].get_id());
+
+    let servers = vec![
+        replica(&lagging, &keys[0]),
+        replica(&server, &keys[1]),
+        replica(&ahead, &keys[2]),
+        replica(&server, &keys[3]),
+        replica(&lagging, &keys[4]),
+    ];
+    // Only three servers have reached the latest epoch of `server`
+    let res = users[0].update(&params, &servers, SERVER_THRESHOLD);
+    assert!(res.is_ok());
+    assert_eq!(users[0].epoch, server.get_epoch());
+    assert!(users[0]
+        .check_witness(&params, &server.get_accumulator())
+        .is_ok());
+
+    // A server asked for an epoch it does not have reports its own
+    let dv = servers[0].update(server.get_epoch(), 1, &[]);
+    assert_eq!(dv.epoch, servers[0].get_epoch());
+    assert!(dv.ds.is_empty());
+
+    // Empty or mismatched answers are rejected instead of crashing the user
+    let UserUpdate {
+        epoch_diff: d,
+        y_shares,
+        y_values,
+    } = users[0]
+        .prepare_for_update(ahead.get_epoch(), 5, SERVER_THRESHOLD)
+        .unwrap();
+    let dvs: Vec<ServerUpdate> = (0..5)
+        .map(|i| ahead.update(ahead.get_epoch(), d, &y_shares[i]))
+        .collect();
+    let old_witness = users[0].witness.as_ref().unwrap().witness;
+    let mut empty = dvs.clone();
+    for dv in empty.iter_mut() {
+        dv.ds.clear();
+        dv.vs.clear();
+    }
+    let mut short = dvs.clone();
+    short[2].ds.pop();
+    let mut uneven = dvs.clone();
+    uneven[3].vs.pop();
+    assert!(users[0]
+        .post_update(old_witness, SERVER_THRESHOLD, &y_shares, &y_values, &dvs)
+        .is_ok());
+    for bad in [empty, short, uneven, dvs[..2].to_vec()] {
+        assert!(users[0]
+            .post_update(old_witness, SERVER_THRESHOLD, &y_shares, &y_values, &bad)
+            .is_err());
+    }
+}
+
+// Tests that an update which does not verify against the signed
+// accumulator is rejected and the old witness is kept
+#[test]
+fn test_witness_update_rejects_invalid() {
+    const SERVERS: usize = 5;
+    const SERVER_THRESHOLD: usize = 3;
+    const USERS: usize = 10;
+    let params = AccParams::default();
+    let mut server = Server::new(&params);
+    let keys = replica_keys(&server, SERVERS);
+    let pinned = pin(&keys);
+    let mut users = Vec::new();
+    for _ in 0..USERS {
+        users.push(User::new(&server, UserID::random(), &pinned).unwrap());
+        server.add(users.last().unwrap().get_id());
+        users.last_mut().unwrap().create_witness(&params, &server);
+    }
+    for user in &users[1..USERS] {
+        server.delete(user.get_id());
+    }
+    let old_witness = users[0].witness.as_ref().unwrap().witness;
+    let old_epoch = users[0].epoch;
+
+    // Every server claims a different latest accumulator
+    let mut servers = replicas(&server, &keys);
+    for s in servers.iter_mut() {
+        *s.accumulators.last_mut().unwrap() = Accumulator::random();
+    }
+    assert!(users[0]
+        .update(&params, &servers, SERVER_THRESHOLD)
+        .is_err());
+    assert_eq!(users[0].witness.as_ref().unwrap().witness, old_witness);
+    assert_eq!(users[0].epoch, old_epoch);
+
+    // A threshold of servers signs a wrong accumulator
+    let mut signed_bad = server.clone();
+    let bad = Accumulator::random();
+    *signed_bad.accumulators.last_mut().unwrap() = bad;
+    let last = signed_bad.heads.len() - 1;
+    signed_bad.heads[last] = EpochHead::new(
+        signed_bad.get_epoch(),
+        bad,
+        Some(&signed_bad.heads[last - 1].head),
+    )
+    .sign(&signed_bad.epoch_secret_key)
+    .unwrap();
+    let servers = replicas(&signed_bad, &keys);
+    assert_eq!(servers[0].get_head().head.accumulator, bad);
+    assert!(users[0]
+        .update(&params, &servers, SERVER_THRESHOLD)
+        .is_err());
+    assert_eq!(users[0].witness.as_ref().unwrap().witness, old_witness);
+    assert_eq!(users[0].epoch, old_epoch);
+
+    let servers = replicas(&server, &keys);
+    assert!(users[0].update(&params, &servers, SERVER_THRESHOLD).is_ok());
+}
+
+// Tests that update policies choose k as expected, that predicted
+// message sizes match the real messages, and that updates still work
+#[test]
+fn test_update_policy() {
+    const SERVERS: usize = 5;
+    const SERVER_THRESHOLD: usize = 3;
+    const USERS: usize = 30;
+    let params = AccParams::default();
+    let mut server = Server::new(&params);
+    let keys = replica_keys(&server, SERVERS);
+    let pinned = pin(&keys);
+    let mut users = Vec::new();
+    for _ in 0..USERS {
+        users.push(User::new(&server, UserID::random(), &pinned).unwrap());
+        server.add(users.last().unwrap().get_id());
+        users.last_mut().unwrap().create_witness(&params, &server);
+    }
+    for user in &users[1..USERS] {
+        server.quick_delete(user.get_id());
+    }
+    let d = USERS - 1;
+    assert_eq!(UpdatePolicy::MinimizeUserOperations.choose_k(d), Ok(d + 1));
+    assert_eq!(UpdatePolicy::Explicit(4).choose_k(d), Ok(4));
+    assert_eq!(UpdatePolicy::Explicit(usize::MAX).choose_k(d), Ok(d + 1));
+    assert!(UpdatePolicy::Explicit(1).choose_k(d).is_err());
+    assert!(UpdatePolicy::MaxUploadBytes(16).choose_k(d).is_err());
+    let cost = UpdateCost::predict(
+        d,
+        SERVERS,
+        SERVER_THRESHOLD,
+        UpdatePolicy::MaxUploadBytes(64),
+    )
+    .unwrap();
+    assert!(cost.upload_bytes <= 64);
+    let balanced =
+        UpdateCost::predict(d, SERVERS, SERVER_THRESHOLD, UpdatePolicy::Balanced).unwrap();
+    let minimal = UpdateCost::predict(
+        d,
+        SERVERS,
+        SERVER_THRESHOLD,
+        UpdatePolicy::MinimizeBandwidth,
+    )
+    .unwrap();
+    assert!(
+        minimal.upload_bytes + minimal.download_bytes
+            <= balanced.upload_bytes + balanced.download_bytes
+    );
+
+    let servers = replicas(&server, &keys);
+    for policy in [
+        UpdatePolicy::Balanced,
+        UpdatePolicy::MinimizeBandwidth,
+        UpdatePolicy::MinimizeUserOperations,
+        UpdatePolicy::MaxUploadBytes(64),
+        UpdatePolicy::Explicit(2),
+    ] {
+        let mut user = users[0].clone();
+        user.update_policy = policy;
+        let cost = user
+            .predict_update(server.get_epoch(), SERVERS, SERVER_THRESHOLD)
+            .unwrap();
+        let UserUpdate {
+            epoch_diff,
+            y_shares,
+            ..
+        } = user
+            .prepare_for_update(server.get_epoch(), SERVERS, SERVER_THRESHOLD)
+            .unwrap();
+        assert_eq!(y_shares[0].len() * 32, cost.upload_bytes);
+        let dv = servers[0].update(server.get_epoch(), epoch_diff, &y_shares[0]);
+        assert_eq!(dv.ds.len(), cost.chunks);
+        assert_eq!(dv.vs.len() * 48 + dv.ds.len() * 32, cost.download_bytes);
+
+        assert!(user.update(&params, &servers, SERVER_THRESHOLD).is_ok());
+        assert!(user
+            .check_witness(&params, &server.get_accumulator())
+            .is_ok());
+    }
+}
+
+// Tests that a server saved without range updates, heads or log
+// rebuilds them to match a server that kept them
+#[test]
+fn test_server_restore() {
+    let params = AccParams::default();
+    let mut server = Server::new(&params);
+    let ids: Vec<UserID> = (0..10).map(|_| UserID::random()).collect();
+    for id in &ids {
+        server.add(*id);
+    }
+    for id in &ids[1..] {
+        server.quick_delete(*id);
+    }
+    let mut restored = server.clone();
+    restored.range_updates = RangeUpdates::default();
+    restored.heads.clear();
+    restored.log = TransparencyLog::default();
+    restored.restore().unwrap();
+
+    let epoch = server.get_epoch();
+    let epoch_key = server.get_epoch_public_key();
+    assert!(SignedEpochHead::verify_chain(&restored.heads, &epoch_key).is_ok());
+    assert_eq!(
+        restored.get_head().head.accumulator,
+        server.get_accumulator()
+    );
+    assert_eq!(restored.log.checkpoint().size, epoch);
+    let updates = restored.range_update(1, epoch).unwrap();
+    let expected = server.range_update(1, epoch).unwrap();
+    assert_eq!(updates.len(), expected.len());
+    for (update, expected) in updates.iter().zip(&expected) {
+        assert_eq!(update.coefficients, expected.coefficients);
+    }
+
+    // Restoring a complete server changes nothing
+    let head = restored.get_head();
+    restored.restore().unwrap();
+    assert_eq!(restored.heads.len(), epoch);
+    assert_eq!(restored.get_head().head, head.head);
+    assert_eq!(restored.log.len(), epoch);
+}
+
+// Tests that with epoch buckets every update request starts at a
+// bucket boundary and the user keeps a valid checkpoint there
+#[test]
+fn test_witness_update_epoch_buckets() {
+    const SERVERS: usize = 5;
+    const SERVER_THRESHOLD: usize = 3;
+    const USERS: usize = 30;
+    const BUCKET: usize = 4;
+    let params = AccParams::default();
+    let mut server = Server::new(&params);
+    let keys = replica_keys(&server, SERVERS);
+    let pinned = pin(&keys);
+    let mut users = Vec::new();
+    for _ in 0..USERS {
+        users.push(User::new(&server, UserID::random(), &pinned).unwrap());
+        server.add(users.last().unwrap().get_id());
+        users.last_mut().unwrap().create_witness(&params, &server);
+    }
+    // The user first updates to an epoch inside a bucket
+    for _ in 0..2 {
+        let y = users.pop().unwrap().get_id();
+        server.delete(y);
+    }
+    let servers = replicas(&server, &keys);
+    assert!(users[0].update(&params, &servers, SERVER_THRESHOLD).is_ok());
+    assert_eq!(users[0].epoch, 3);
+
+    users[0].epoch_bucket = Some(BUCKET);
+    for round in [3, 2, 11, 1, 4] {
+        for _ in 0..round {
+            let y = users.pop().unwrap().get_id();
+            server.quick_delete(y);
+        }
+        let new_epoch = server.get_epoch();
+        let plan = users[0].update_plan(new_epoch);
+        for (i, (from, to)) in plan.iter().enumerate() {
+            assert!((from - 1) % BUCKET == 0);
+            // All but the last request span bucket * 2^i epochs aligned to their size
+            let span = to - from;
+            if i + 1 < plan.len() || (to - 1) % BUCKET == 0 {
+                assert!((span / BUCKET).is_power_of_two() && span % BUCKET == 0);
+                assert!((from - 1) % span == 0);
+            } else {
+                assert!(span < BUCKET);
+            }
+        }
+        let servers = replicas(&server, &keys);
+        assert!(users[0].update(&params, &servers, SERVER_THRESHOLD).is_ok());
+        assert!(users[0]
+            .check_witness(&params, &server.get_accumulator())
+            .is_ok());
+    }
+    let checkpoint = users[0].checkpoint.unwrap();
+    assert_eq!((checkpoint.epoch - 1) % BUCKET, 0);
+    assert_eq!(
+        server.get_accumulator_at(checkpoint.epoch),
+        Some(checkpoint.accumulator)
+    );
+    assert!(checkpoint.witness.verify(
+        users[0].get_id(),
+        server.get_witness_public_key(),
+        checkpoint.accumulator
+    ));
+}
+
+// Tests that an update can be relayed to the servers sealed,
+// and that a relay cannot tamper with the sealed messages
+#[test]
+fn test_sealed_witness_update() {
+    const SERVERS: usize = 5;
+    const SERVER_THRESHOLD: usize = 3;
+    const USERS: usize = 10;
+    let params = AccParams::default();
+    let (mut server, mut users) = setup(&params, USERS);
+    for user in &users[1..USERS] {
+        server.delete(user.get_id());
+    }
+    let servers: Vec<Server> = (0..SERVERS)
+        .map(|_| {
+            let mut s = server.clone();
+            s.encryption_secret_key = SecretKey::new(None);
+            s
+        })
+        .collect();
+    let server_keys: Vec<EncryptionPublicKey> = servers
+        .iter()
+        .map(|s| s.get_encryption_public_key())
+        .collect();
+    // Each server publishes its own encryption key apart from the shared public keys
+    assert_ne!(server_keys[0], server_keys[1]);
+
+    let epoch = server.get_epoch();
+    let update = users[0]
+        .prepare_for_update(epoch, SERVERS, SERVER_THRESHOLD)
+        .unwrap();
+    let (requests, response_keys) = update.seal(epoch, &server_keys).unwrap();
+
+    // A request can only be opened by the server it is sealed to
+    assert!(servers[1].sealed_update(&requests[0]).is_err());
+    let mut tampered = requests[0].clone();
+    tampered.ciphertext[0] ^= 1;
+    assert!(servers[0].sealed_update(&tampered).is_err());
+
+    let responses: Vec<SealedResponse> = (0..SERVERS)
+        .map(|i| servers[i].sealed_update(&requests[i]).unwrap())
+        .collect();
+    let mut tampered = responses[0].clone();
+    tampered.tag[0] ^= 1;
+    assert!(response_keys[0].open(&tampered).is_err());
+    assert!(response_keys[1].open(&responses[0]).is_err());
+
+    let dvs: Vec<ServerUpdate> = (0..SERVERS)
+        .map(|i| response_keys[i].open(&responses[i]).unwrap())
+        .collect();
+    let res = users[0].post_update(
+        users[0].witness.as_ref().unwrap().witness,
+        SERVER_THRESHOLD,
+        &update.y_shares,
+        &update.y_values,
+        &dvs,
+    );
+    assert!(res.is_ok());
+    if let Some(w) = users[0].witness.as_mut() {
+        w.witness = res.unwrap();
+    }
+    assert!(users[0]
+        .check_witness(&params, &server.get_accumulator())
+        .is_ok());
+}
+
+// Tests that servers publish a signed chain of epoch heads
+// and that accumulators are only accepted with a valid head
+#[test]
+fn test_epoch_heads() {
+    let params = AccParams::default();
+    let (mut server, mut users) = setup(&params, 5);
+    for user in &users[1..5] {
+        server.delete(user.get_id());
+    }
+    let epoch_key = server.get_epoch_public_key();
+    assert_eq!(server.heads.len(), server.get_epoch());
+    assert!(SignedEpochHead::verify_chain(&server.heads, &epoch_key).is_ok());
+    assert_eq!(
+        server.get_head().accumulator(&epoch_key),
+        Ok(server.get_accumulator())
+    );
+
+    // A changed accumulator or a gap in the chain is rejected
+    let mut forged = server.get_head();
+    forged.head.accumulator = Accumulator(forged.head.accumulator.0.double());
+    assert!(forged.accumulator(&epoch_key).is_err());
+    let mut gap = server.heads.clone();
+    gap.remove(2);
+    assert!(SignedEpochHead::verify_chain(&gap, &epoch_key).is_err());
+
+    // Verifiers check proofs against the signed head
+    let mut ephemeral_challenge = [0u8; 2 * SECURITY_BYTES];
+    rand::rngs::OsRng.fill_bytes(&mut ephemeral_challenge);
+    users[0].range_update(&params, &server).unwrap();
+    let proof = users[0]
+        .make_membership_proof(&params, &server.get_public_keys(), &ephemeral_challenge)
+        .unwrap();
+    assert!(Witness::check_membership_proof_at_head(
+        &proof,
+        &params,
+        &server.get_public_keys(),
+        &server.get_head(),
+        &epoch_key,
+        &ephemeral_challenge
+    ));
+    assert!(!Witness::check_membership_proof_at_head(
+        &proof,
+        &params,
+        &server.get_public_keys(),
+        &server.get_head(),
+        &EpochSecretKey::new().public_key(),
+        &ephemeral_challenge
+    ));
+
+    // Users refuse an accumulator whose head is not signed under a pinned key
+    let other = replica(&server, &EpochSecretKey::new());
+    let mut user = users[0].clone();
+    user.epoch = 1;
+    assert!(user.range_update(&params, &other).is_err());
+    assert_eq!(user.epoch, 1);
+    let pinned = [server.get_epoch_public_key()];
+    assert!(User::new(&other, UserID::random(), &pinned).is_err());
+    let mut user = User::new(&server, UserID::random(), &pinned).unwrap();
+    let mut other = other;
+    other.add(user.get_id());
+    user.create_witness(&params, &other);
+    user.create_non_membership_witness(&params, &other);
+    assert!(user.witness.is_none() && user.non_membership.is_none());
+}
+
+// Tests that a threshold of servers certify an epoch head under a joint key
+#[test]
+fn test_threshold_epoch_certificate() {
+    const SERVERS: usize = 5;
+    const SERVER_THRESHOLD: usize = 3;
+    let params = AccParams::default();
+    let (mut server, mut users) = setup(&params, 5);
+    server.delete(users[1].get_id());
+
+    let joint_secret_key = EpochSecretKey::new();
+    let joint_key = joint_secret_key.public_key();
+    let key_shares = split_epoch_key(&joint_secret_key, SERVER_THRESHOLD, SERVERS).unwrap();
+    let mut servers: Vec<Server> = key_shares
+        .into_iter()
+        .map(|share| {
+            let mut s = server.clone();
+            s.epoch_key_share = Some(share);
+            s
+        })
+        .collect();
+
+    // Any threshold of the servers certify the first head
+    let first = server.get_epoch_head(1).unwrap().head;
+    let shares: Vec<EpochSignatureShare> = servers
+        .iter()
+        .map(|s| s.sign_head_share(&first).unwrap())
+        .collect();
+    let first_certificate = first.certify(&shares[2..], &joint_key).unwrap();
+    assert!(servers[0]
+        .store_certificate(
+            &first.sign(&joint_secret_key).unwrap(),
+            &EpochSecretKey::new().public_key()
+        )
+        .is_err());
+    for s in servers.iter_mut() {
+        s.store_certificate(&first_certificate, &joint_key).unwrap();
+    }
+
+    // A head must follow the last certified head in epoch, link and time
+    let head = server.get_head().head;
+    let mut unlinked = head;
+    unlinked.previous = [1u8; 32];
+    assert!(servers[1].sign_head_share(&unlinked).is_err());
+    let mut earlier = head;
+    earlier.timestamp = first.timestamp - 1;
+    assert!(servers[1].sign_head_share(&earlier).is_err());
+    let mut future = head;
+    future.timestamp += 3600;
+    assert!(servers[1].sign_head_share(&future).is_err());
+    assert!(servers[1].sign_head_share(&first).is_err());
+
+    let shares: Vec<EpochSignatureShare> = servers
+        .iter()
+        .map(|s| s.sign_head_share(&head).unwrap())
+        .collect();
+    for (s, share) in servers.iter().zip(shares.iter()) {
+        let public_key_share = s.get_epoch_public_key_share().unwrap();
+        assert!(head.verify_share(&public_key_share, share).is_ok());
+    }
+    let certificate = head.certify(&shares[2..], &joint_key).unwrap();
+    assert_eq!(
+        certificate.accumulator(&joint_key),
+        Ok(server.get_accumulator())
+    );
+    assert!(head.certify(&shares[..2], &joint_key).is_err());
+    for s in servers.iter_mut() {
+        s.store_certificate(&certificate, &joint_key).unwrap();
+    }
+    assert_eq!(servers[1].get_certificate(2).unwrap().head, head);
+
+    // A server refuses to sign a head that does not match its accumulator
+    servers[0].delete(users[2].get_id());
+    let forged = EpochHead::new(3, servers[0].get_accumulator(), Some(&head));
+    assert!(servers[1].sign_head_share(&forged).is_err());
+    assert!(servers[0].sign_head_share(&forged).is_ok());
+
+    // Verifiers check a single certificate
+    let mut ephemeral_challenge = [0u8; 2 * SECURITY_BYTES];
+    rand::rngs::OsRng.fill_bytes(&mut ephemeral_challenge);
+    users[0].range_update(&params, &server).unwrap();
+    let proof = users[0]
+        .make_membership_proof(&params, &server.get_public_keys(), &ephemeral_challenge)
+        .unwrap();
+    assert!(Witness::check_membership_proof_at_head(
+        &proof,
+        &params,
+        &server.get_public_keys(),
+        &certificate,
+        &joint_key,
+        &ephemeral_challenge
+    ));
+}
+
+// Tests inclusion and consistency proofs of the epoch log
+// for every pair of sizes, and that rewritten history is caught
+#[test]
+fn test_transparency_log() {
+    let params = AccParams::default();
+    let mut server = Server::new(&params);
+    let mut users = Vec::new();
+    for _ in 0..12 {
+        users.push(User::new(&server, UserID::random(), &[server.get_epoch_public_key()]).unwrap());
+        server.add(users.last().unwrap().get_id());
+    }
+    for user in &users[1..] {
+        server.quick_delete(user.get_id());
+    }
+    let log = &server.log;
+    let key = server.get_epoch_public_key();
+    let other_key = EpochSecretKey::new();
+    assert_eq!(log.len(), server.get_epoch());
+    assert_eq!(log.checkpoint(), log.checkpoint_at(log.len()).unwrap());
+    assert_eq!(server.get_log_checkpoint().checkpoint, log.checkpoint());
+
+    for new_size in 1..=log.len() {
+        let new = server.get_log_checkpoint_at(new_size).unwrap();
+        for epoch in 1..=new_size {
+            let proof = log.inclusion_proof(epoch, new_size).unwrap();
+            let head = server.get_epoch_head(epoch).unwrap().head;
+            assert!(proof.verify(&head, &new.checkpoint));
+            let other = server.get_epoch_head(epoch % new_size + 1).unwrap().head;
+            assert_eq!(proof.verify(&other, &new.checkpoint), new_size == 1);
+        }
+        for old_size in 0..=new_size {
+            let old = server.get_log_checkpoint_at(old_size).unwrap();
+            let proof = log.consistency_proof(old_size, new_size).unwrap();
+            assert!(proof.verify(&old, &new, &key));
+            if old_size > 0 && old_size < new_size {
+                let mut bad = proof.clone();
+                bad.path[0][0] ^= 1;
+                assert!(!bad.verify(&old, &new, &key));
+            }
+            // Checkpoints the server did not sign are not attributed to it
+            let unsigned = old.checkpoint.sign(&other_key).unwrap();
+            assert!(!proof.verify(&unsigned, &new, &key));
+        }
+    }
+
+    // A log that rewrites an old epoch is not an extension of the old log
+    let old = server.get_log_checkpoint_at(5).unwrap();
+    let mut forked = TransparencyLog::default();
+    for epoch in 1..=server.get_epoch() {
+        let mut head = server.get_epoch_head(epoch).unwrap().head;
+        if epoch == 3 {
+            head.accumulator = Accumulator(head.accumulator.0.double());
+        }
+        forked.append(&head).unwrap();
+    }
+    let forked_checkpoint = forked.checkpoint().sign(&server.epoch_secret_key).unwrap();
+    let proof = forked.consistency_proof(5, forked.len()).unwrap();
+    assert!(!proof.verify(&old, &forked_checkpoint, &key));
+    assert!(forked.append(&server.get_head().head).is_err());
+
+    // The server's two signed checkpoints for the same size prove the fork
+    let honest = server.get_log_checkpoint();
+    assert!(LogForkProof::new(honest, server.get_log_checkpoint(), &key).is_none());
+    let proof = LogForkProof::new(honest, forked_checkpoint, &key).unwrap();
+    let bytes = serde_bare::to_vec(&proof).unwrap();
+    let proof: LogForkProof = serde_bare::from_slice(&bytes).unwrap();
+    assert!(proof.verify(&key));
+    assert!(!proof.verify(&other_key.public_key()));
+    let unsigned = forked.checkpoint().sign(&other_key).unwrap();
+    assert!(LogForkProof::new(honest, unsigned, &key).is_none());
+}
+
+// Tests that users catch a server tagging them with a unique accumulator,
+// and that the server's conflicting heads give a portable fork proof
+#[test]
+fn test_fork_detection() {
+    const SERVERS: usize = 3;
+    const SERVER_THRESHOLD: usize = 2;
+    let params = AccParams::default();
+    let mut server = Server::new(&params);
+    let keys = replica_keys(&server, SERVERS);
+    let pinned = pin(&keys);
+    let mut users = Vec::new();
+    for _ in 0..5 {
+        users.push(User::new(&server, UserID::random(), &pinned).unwrap());
+        server.add(users.last().unwrap().get_id());
+        users.last_mut().unwrap().create_witness(&params, &server);
+    }
+    server.delete(users[4].get_id());
+    let servers = replicas(&server, &keys);
+    let epoch = server.get_epoch();
+    let heads: Vec<SignedEpochHead> = servers.iter().map(|s| s.get_head()).collect();
+
+    users[0].range_update(&params, &servers[0]).unwrap();
+    assert!(users[0].check_accumulator(&heads, SERVER_THRESHOLD).is_ok());
+
+    // Copies of one server's head and heads under unpinned keys do not count
+    let copies = [servers[0].get_head(); SERVER_THRESHOLD];
+    assert!(users[0]
+        .check_accumulator(&copies, SERVER_THRESHOLD)
+        .is_err());
+    let unpinned = replicas(&server, &replica_keys(&server, SERVERS)[1..]);
+    let mut heads_unpinned = vec![servers[0].get_head()];
+    heads_unpinned.extend(unpinned.iter().map(|s| s.get_head()));
+    assert!(users[0]
+        .check_accumulator(&heads_unpinned, SERVER_THRESHOLD)
+        .is_err());
+
+    // The first server gives the second user a unique accumulator
+    let key = servers[0].get_epoch_public_key();
+    let honest = servers[0].get_head();
+    let mut tagged_head = honest.head;
+    tagged_head.accumulator = Accumulator(honest.head.accumulator.0.double());
+    let tagged = tagged_head.sign(&servers[0].epoch_secret_key).unwrap();
+    users[1].epoch = epoch;
+    users[1].accumulator = tagged.accumulator(&key).unwrap();
+    assert!(users[1]
+        .check_accumulator(&heads, SERVER_THRESHOLD)
+        .is_err());
+
+    // Comparing the heads they were given exposes the server
+    assert!(ForkProof::find(&heads, &key).is_none());
+    let proof = ForkProof::find(&[honest, servers[0].heads[0], tagged], &key).unwrap();
+    let bytes = serde_bare::to_vec(&proof).unwrap();
+    let proof: ForkProof = serde_bare::from_slice(&bytes).unwrap();
+    assert!(proof.verify(&key));
+    assert!(!proof.verify(&servers[1].get_epoch_public_key()));
+    assert!(ForkProof::new(honest, honest, &key).is_none());
+}
+
+// Tests that every epoch transition has a publicly verifiable deletion receipt
+#[test]
+fn test_deletion_receipts() {
+    let params = AccParams::default();
+    let mut server = Server::new(&params);
+    let mut users = Vec::new();
+    for _ in 0..6 {
+        users.push(User::new(&server, UserID::random(), &[server.get_epoch_public_key()]).unwrap());
+        server.add(users.last().unwrap().get_id());
+    }
+    server.delete(users[1].get_id());
+    server.delete(users[2].get_id());
+    server.quick_delete(users[3].get_id());
+    let witness_key = server.get_witness_public_key();
+
+    let receipts = server.deletion_receipts();
+    assert_eq!(receipts.len(), 3);
+    assert!(server.deletion_receipt(1).is_none());
+    assert!(server.deletion_receipt(server.get_epoch() + 1).is_none());
+    assert_eq!(receipts[2].accumulator, server.get_accumulator());
+    assert!(receipts.iter().all(|r| r.verify(&params, &witness_key)));
+    assert!(DeletionReceipt::verify_chain(&receipts, &params, &witness_key).is_ok());
+
+    // A transition that was not a deletion of the claimed element fails
+    let mut wrong = receipts[1];
+    wrong.deleted = users[4].get_id();
+    assert!(!wrong.verify(&params, &witness_key));
+    let mut wrong = receipts.clone();
+    wrong[1].deleted = users[4].get_id();
+    assert!(DeletionReceipt::verify_chain(&wrong, &params, &witness_key).is_err());
+    // As does a chain with a missing epoch
+    let gap = [receipts[0], receipts[2]];
+    assert!(DeletionReceipt::verify_chain(&gap, &params, &witness_key).is_err());
+}
+
+// Tests that the audit passes a consistent server
+// and reports each corruption of its state
+#[test]
+fn test_server_audit() {
+    let params = AccParams::default();
+    let mut server = Server::new(&params);
+    let users: Vec<UserID> = (0..20).map(|_| UserID::random()).collect();
+    for y in &users {
+        server.add(*y);
+    }
+    for y in &users[..4] {
+        server.delete(*y);
+    }
+    assert!(server.audit(&params).is_empty());
+
+    let mut corrupted = server.clone();
+    let bad_witness = users[7];
+    corrupted.all_witnesses.get_mut(&bad_witness).unwrap().0 = G1Projective::GENERATOR;
+    corrupted.all_users.remove(&users[9]);
+    corrupted.all_witnesses.remove(&users[11]);
+    corrupted.deletions[1] = users[12];
+    corrupted.public_keys.sign_key = server.public_keys.witness_key;
+    corrupted.heads[3] = corrupted.heads[3]
+        .head
+        .sign(&EpochSecretKey::new())
+        .unwrap();
+    let found = corrupted.audit(&params);
+    let expected = [
+        Discrepancy::SignKeyMismatch,
+        Discrepancy::InvalidWitness(bad_witness),
+        Discrepancy::WitnessWithoutUser(users[9]),
+        Discrepancy::UserWithoutWitness(users[11]),
+        Discrepancy::DeletedUserWithWitness(users[12]),
+        Discrepancy::UserWithoutWitness(users[1]),
+        Discrepancy::InvalidDeletion(3),
+        Discrepancy::InvalidHead(4),
+    ];
+    for d in &expected {
+        assert!(found.contains(d), "missing {:?}", d);
+    }
+    assert_eq!(found.len(), expected.len());
+
+    // Dropping an epoch breaks the counts, the heads and the log
+    let mut rewritten = server.clone();
+    rewritten.accumulators.remove(2);
+    let found = rewritten.audit(&params);
+    assert!(found.contains(&Discrepancy::EpochCountMismatch {
+        accumulators: 4,
+        deletions: 4
+    }));
+    assert!(found.contains(&Discrepancy::InvalidHead(3)));
+    assert!(found.contains(&Discrepancy::InvalidHead(5)));
+    rewritten.log = TransparencyLog::default();
+    assert!(rewritten.audit(&params).contains(&Discrepancy::LogMismatch));
+}
+
+// Tests that the verifier manages challenges and accepted heads
+#[test]
+fn test_verifier() {
+    let params = AccParams::default();
+    let (mut server, users) = setup(&params, 3);
+    let mut verifier = Verifier::new(
+        params,
+        server.get_public_keys(),
+        server.get_epoch_public_key(),
+        std::time::Duration::from_secs(60),
+    );
+    let challenge = verifier.issue_challenge();
+    let proof = users[0]
+        .make_membership_proof(&params, &server.get_public_keys(), &challenge)
+        .unwrap();
+    assert_eq!(
+        verifier.verify(&proof, &challenge),
+        VerificationResult::NoAcceptedHead
+    );
+
+    // Heads must be validly signed and newer than the current one
+    let mut forged = server.get_head();
+    forged.head.accumulator = Accumulator(forged.head.accumulator.0.double());
+    assert!(verifier.accept_head(forged).is_err());
+    assert!(verifier.accept_head(server.get_head()).is_ok());
+    assert!(verifier.accept_head(server.get_head()).is_err());
+
+    let challenge = verifier.issue_challenge();
+    let proof = users[0]
+        .make_membership_proof(&params, &server.get_public_keys(), &challenge)
+        .unwrap();
+    assert_eq!(
+        verifier.verify(&proof, &challenge),
+        VerificationResult::Accepted { epoch: 1 }
+    );
+    assert_eq!(
+        verifier.verify(&proof, &challenge),
+        VerificationResult::ReplayedChallenge
+    );
+    let mut unknown = challenge;
+    unknown[0] ^= 1;
+    assert_eq!(
+        verifier.verify(&proof, &unknown),
+        VerificationResult::UnknownChallenge
+    );
+    let challenge = verifier.issue_challenge();
+    assert_eq!(
+        verifier.verify(&proof, &challenge),
+        VerificationResult::InvalidProof
+    );
+
+    // A deleted user is rejected once the verifier accepts the new head
+    server.delete(users[1].get_id());
+    assert!(verifier.accept_head(server.get_head()).is_ok());
+    let challenge = verifier.issue_challenge();
+    let proof = users[1]
+        .make_membership_proof(&params, &server.get_public_keys(), &challenge)
+        .unwrap();
+    assert!(!verifier.verify(&proof, &challenge).is_accepted());
+
+    // Challenges expire
+    let mut verifier = Verifier::new(
+        params,
+        server.get_public_keys(),
+        server.get_epoch_public_key(),
+        std::time::Duration::ZERO,
+    );
+    verifier.accept_head(server.get_head()).unwrap();
+    let challenge = verifier.issue_challenge();
+    assert_eq!(
+        verifier.verify(&proof, &challenge),
+        VerificationResult::ExpiredChallenge
+    );
+}
+
+// Tests that verifiers accept proofs for a window of recent epochs,
+// that the window can change after heads are accepted
+// and that a proof's epoch is bound to it
+#[test]
+fn test_verifier_epoch_window() {
+    let params = AccParams::default();
+    let (mut server, mut users) = setup(&params, 6);
+    let mut verifier = Verifier::new(
+        params,
+        server.get_public_keys(),
+        server.get_epoch_public_key(),
+        std::time::Duration::from_secs(60),
+    );
+    assert!(verifier.set_epoch_window(0).is_err());
+    verifier.set_epoch_window(3).unwrap();
+    verifier.accept_head(server.get_head()).unwrap();
+    for user in &users[2..6] {
+        server.delete(user.get_id());
+        verifier.accept_head(server.get_head()).unwrap();
+    }
+    assert_eq!(server.get_epoch(), 5);
+
+    let challenge = verifier.issue_challenge();
+    let proof = users[0]
+        .make_membership_proof(&params, &server.get_public_keys(), &challenge)
+        .unwrap();
+    assert_eq!(proof.epoch(), 1);
+    assert_eq!(
+        verifier.verify(&proof, &challenge),
+        VerificationResult::EpochNotAccepted { epoch: 1 }
+    );
+
+    // A user whose witness is two epochs old
+    users[0].range_update(&params, &server).unwrap();
+    let mut stale = users[0].clone();
+    stale.epoch = 3;
+    stale.accumulator = server.get_accumulator_at(3).unwrap();
+    stale.witness.as_mut().unwrap().witness = server
+        .get_accumulator_at(3)
+        .map(|v| {
+            MembershipWitness(v.0 * (stale.id.0 + server.witness_secret_key.0).invert().unwrap())
+        })
+        .unwrap();
+    let challenge = verifier.issue_challenge();
+    let proof = stale
+        .make_membership_proof(&params, &server.get_public_keys(), &challenge)
+        .unwrap();
+    assert_eq!(
+        verifier.verify(&proof, &challenge),
+        VerificationResult::Accepted { epoch: 3 }
+    );
+
+    // Narrowing the window rejects the stale proof, and widening it again
+    // accepts it without the head being accepted again
+    verifier.set_epoch_window(1).unwrap();
+    let challenge = verifier.issue_challenge();
+    let proof = stale
+        .make_membership_proof(&params, &server.get_public_keys(), &challenge)
+        .unwrap();
+    assert_eq!(
+        verifier.verify(&proof, &challenge),
+        VerificationResult::EpochNotAccepted { epoch: 3 }
+    );
+    verifier.set_epoch_window(3).unwrap();
+    let challenge = verifier.issue_challenge();
+    let proof = stale
+        .make_membership_proof(&params, &server.get_public_keys(), &challenge)
+        .unwrap();
+    assert_eq!(
+        verifier.verify(&proof, &challenge),
+        VerificationResult::Accepted { epoch: 3 }
+    );
+
+    // A verifier that only saw the latest head accepts older heads inside its window
+    let mut late = Verifier::new(
+        params,
+        server.get_public_keys(),
+        server.get_epoch_public_key(),
+        std::time::Duration::from_secs(60),
+    );
+    late.accept_head(server.get_head()).unwrap();
+    assert!(late.accept_head(server.get_epoch_head(3).unwrap()).is_err());
+    late.set_epoch_window(3).unwrap();
+    assert!(late.accept_head(server.get_epoch_head(2).unwrap()).is_err());
+    late.accept_head(server.get_epoch_head(3).unwrap()).unwrap();
+    assert!(late.accept_head(server.get_epoch_head(3).unwrap()).is_err());
+    assert_eq!(late.get_head().unwrap().head, server.get_head().head);
+    let challenge = late.issue_challenge();
+    let proof = stale
+        .make_membership_proof(&params, &server.get_public_keys(), &challenge)
+        .unwrap();
+    assert_eq!(
+        late.verify(&proof, &challenge),
+        VerificationResult::Accepted { epoch: 3 }
+    );
+
+    // Changing the epoch of a proof invalidates it
+    let challenge = verifier.issue_challenge();
+    let proof = users[0]
+        .make_membership_proof(&params, &server.get_public_keys(), &challenge)
+        .unwrap();
+    let mut bytes = proof.to_bytes();
+    bytes[MembershipProof::BYTES - 1] ^= 1;
+    let moved = MembershipProof::from_bytes(&bytes).unwrap();
+    assert_eq!(moved.epoch(), 4);
+    assert!(!Witness::check_membership_proof(
+        &moved,
+        &params,
+        &server.get_public_keys(),
+        &server.get_accumulator(),
+        &challenge
+    ));
+    assert_eq!(
+        verifier.verify(&proof, &challenge),
+        VerificationResult::Accepted { epoch: 5 }
+    );
+}
+
+// Tests that a batch of membership proofs is checked at once
+// and that invalid proofs in it are located
+#[test]
+fn test_membership_proof_batch() {
+    let params = AccParams::default();
+    let (server, users) = setup(&params, 8);
+    let public_keys = server.get_public_keys();
+    let accumulator = server.get_accumulator();
+    let mut batch = MembershipProofBatch::new(params, public_keys);
+    assert!(batch.is_empty());
+    assert!(batch.verify().is_empty());
+
+    let mut proofs = Vec::new();
+    for user in &users {
+        let mut challenge = [0u8; 2 * SECURITY_BYTES];
+        rand::rngs::OsRng.fill_bytes(&mut challenge);
+        let proof = user
+            .make_committed_membership_proof(&params, &public_keys, &challenge)
+            .unwrap();
+        assert!(Witness::check_membership_proof(
+            &proof.proof(),
+            &params,
+            &public_keys,
+            &accumulator,
+            &challenge
+        ));
+        proofs.push((proof, challenge));
+    }
+    for (proof, challenge) in &proofs {
+        batch.add(*proof, accumulator, *challenge);
+    }
+    assert_eq!(batch.len(), 8);
+    assert!(batch.verify().is_empty());
+
+    // A wrong challenge, a wrong accumulator and a changed commitment
+    let mut batch = MembershipProofBatch::new(params, public_keys);
+    for (i, (proof, challenge)) in proofs.iter().enumerate() {
+        let mut proof = *proof;
+        let mut challenge = *challenge;
+        let mut accumulator = accumulator;
+        match i {
+            1 => challenge[0] ^= 1,
+            4 => accumulator = Accumulator::random(),
+            6 => proof.proof.s_3 += Scalar::ONE,
+            _ => {}
+        }
+        batch.add(proof, accumulator, challenge);
+    }
+    assert_eq!(batch.verify(), vec![1, 4, 6]);
+}
+
+// Tests non-membership witnesses, their updates from deletion receipts,
+// and non-membership proofs
+#[test]
+fn test_non_membership() {
+    let params = AccParams::default();
+    let mut server = Server::new(&params);
+    let mut users = Vec::new();
+    for _ in 0..5 {
+        users.push(User::new(&server, UserID::random(), &[server.get_epoch_public_key()]).unwrap());
+        server.add(users.last().unwrap().get_id());
+    }
+    server.delete(users[0].get_id());
+
+    // Deleted users get no witness
+    users[0].create_non_membership_witness(&params, &server);
+    assert!(users[0].non_membership.is_none());
+    for user in users.iter_mut().skip(1) {
+        user.create_non_membership_witness(&params, &server);
+        user.non_membership
+            .as_ref()
+            .unwrap()
+            .verify(&server.get_public_keys(), &params, &user.get_id())
+            .unwrap();
+    }
+
+    // Updates need no help from the server beyond the public receipts
+    server.delete(users[1].get_id());
+    server.delete(users[2].get_id());
+    assert_eq!(
+        users[1].non_membership_update(&params, &server),
+        Err("deleted")
+    );
+    users[3].non_membership_update(&params, &server).unwrap();
+    assert_eq!(users[3].non_membership.as_ref().unwrap().epoch, 4);
+
+    let public_keys = server.get_public_keys();
+    let initial = server.get_accumulator_at(1).unwrap();
+    let mut challenge = [0u8; 2 * SECURITY_BYTES];
+    rand::rngs::OsRng.fill_bytes(&mut challenge);
+    let proof = users[3]
+        .make_non_membership_proof(&params, &public_keys, &challenge)
+        .unwrap();
+    assert_eq!(proof.epoch(), 4);
+    assert!(NonWitness::check_non_membership_proof(
+        &proof,
+        &params,
+        &public_keys,
+        &initial,
+        &server.get_accumulator(),
+        &challenge
+    ));
+    let proof = NonMembershipProof::from_bytes(&proof.to_bytes()).unwrap();
+    assert!(NonWitness::check_non_membership_proof(
+        &proof,
+        &params,
+        &public_keys,
+        &initial,
+        &server.get_accumulator(),
+        &challenge
+    ));
+    // Wrong accumulator or challenge
+    assert!(!NonWitness::check_non_membership_proof(
+        &proof,
+        &params,
+        &public_keys,
+        &initial,
+        &server.get_accumulator_at(3).unwrap(),
+        &challenge
+    ));
+    challenge[0] ^= 1;
+    assert!(!NonWitness::check_non_membership_proof(
+        &proof,
+        &params,
+        &public_keys,
+        &initial,
+        &server.get_accumulator(),
+        &challenge
+    ));
+
+    // A stale witness still proves non-membership at its own epoch
+    let stale = users[4]
+        .make_non_membership_proof(&params, &public_keys, &challenge)
+        .unwrap();
+    assert_eq!(stale.epoch(), 2);
+    assert!(NonWitness::check_non_membership_proof(
+        &stale,
+        &params,
+        &public_keys,
+        &initial,
+        &server.get_accumulator_at(2).unwrap(),
+        &challenge
+    ));
+}
+
+// Tests that presentations sign their whole message
+#[test]
+fn test_presentation() {
+    let params = AccParams::default();
+    let (server, mut users) = setup(&params, 1);
+    let user = users.pop().unwrap();
+    let public_keys = server.get_public_keys();
+    let accumulator = server.get_accumulator();
+
+    let message = PresentationMessage::new(b"example.org/login")
+        .with_audience(b"example.org")
+        .with_nonce(b"0123456789")
+        .with_body(b"{\"action\":\"login\"}");
+    let proof = user
+        .make_presentation(&params, &public_keys, &message)
+        .unwrap();
+    assert!(Witness::check_presentation(
+        &proof,
+        &params,
+        &public_keys,
+        &accumulator,
+        &message
+    ));
+
+    // Changing any part of the message invalidates the presentation
+    let others = [
+        PresentationMessage {
+            domain: DEFAULT_PRESENTATION_DOMAIN.to_vec(),
+            ..message.clone()
+        },
+        message.clone().with_audience(b"example.com"),
+        message.clone().with_nonce(b"9876543210"),
+        message.clone().with_body(b"{\"action\":\"delete\"}"),
+    ];
+    for other in &others {
+        assert!(!Witness::check_presentation(
+            &proof,
+            &params,
+            &public_keys,
+            &accumulator,
+            other
+        ));
+    }
+    // A presentation is not a membership proof for any challenge
+    let mut challenge = [0u8; 2 * SECURITY_BYTES];
+    challenge[..10].copy_from_slice(b"0123456789");
+    assert!(!Witness::check_membership_proof(
+        &proof,
+        &params,
+        &public_keys,
+        &accumulator,
+        &challenge
+    ));
+}
+
+// Tests that scoped proofs output one pseudonym per user and scope
+#[test]
+fn test_scoped_pseudonyms() {
+    let params = AccParams::default();
+    let (server, users) = setup(&params, 2);
+    let public_keys = server.get_public_keys();
+    let accumulator = server.get_accumulator();
+    let prove = |user: &User, scope: &[u8]| {
+        let mut challenge = [0u8; 2 * SECURITY_BYTES];
+        rand::rngs::OsRng.fill_bytes(&mut challenge);
+        let proof = user
+            .make_scoped_membership_proof(&params, &public_keys, scope, &challenge)
+            .unwrap();
+        assert!(proof.verify(&params, &public_keys, &accumulator, scope, &challenge));
+        (proof, challenge)
+    };
+
+    let (first, challenge) = prove(&users[0], b"forum.example");
+    let (second, _) = prove(&users[0], b"forum.example");
+    let (other_scope, _) = prove(&users[0], b"shop.example");
+    let (other_user, _) = prove(&users[1], b"forum.example");
+    let pseudonym = first.pseudonym();
+    assert_eq!(second.pseudonym(), pseudonym);
+    assert_ne!(other_scope.pseudonym(), pseudonym);
+    assert_ne!(other_user.pseudonym(), pseudonym);
+
+    // The proof only verifies for its own scope and pseudonym
+    assert!(!first.verify(
+        &params,
+        &public_keys,
+        &accumulator,
+        b"shop.example",
+        &challenge
+    ));
+    let mut moved = first;
+    moved.pseudonym = other_user.pseudonym;
+    assert!(!moved.verify(
+        &params,
+        &public_keys,
+        &accumulator,
+        b"forum.example",
+        &challenge
+    ));
+    // The inner proof is not a plain membership proof
+    assert!(!Witness::check_membership_proof(
+        &first.proof(),
+        &params,
+        &public_keys,
+        &accumulator,
+        &challenge
+    ));
+    assert_eq!(MembershipProof::BYTES, 440);
+    let bytes = ScopedMembershipProof::from_bytes(&first.to_bytes()).unwrap();
+    assert_eq!(bytes.pseudonym(), pseudonym);
+    assert!(bytes.verify(
+        &params,
+        &public_keys,
+        &accumulator,
+        b"forum.example",
+        &challenge
+    ));
+}
+
+// Tests that k-times presentations reveal a serial number that repeats only past the limit
+#[test]
+fn test_k_times_presentations() {
+    let params = AccParams::default();
+    let (server, users) = setup(&params, 2);
+    let public_keys = server.get_public_keys();
+    let accumulator = server.get_accumulator();
+    let today = UsageLimit::new(b"api.example", 20, 3);
+    let tomorrow = UsageLimit::new(b"api.example", 21, 3);
+    let present = |user: &User, limit: &UsageLimit, counter: usize| {
+        let mut challenge = [0u8; 2 * SECURITY_BYTES];
+        rand::rngs::OsRng.fill_bytes(&mut challenge);
+        let presentation = user
+            .make_k_times_presentation(&params, &public_keys, limit, counter, &challenge)
+            .unwrap();
+        assert!(presentation.verify(&params, &public_keys, &accumulator, limit, &challenge));
+        (presentation, challenge)
+    };
+
+    // Reusing a counter repeats the serial; everything else looks fresh
+    let (first, challenge) = present(&users[0], &today, 0);
+    let (again, _) = present(&users[0], &today, 0);
+    assert_eq!(first.serial(), again.serial());
+    let (other_user, _) = present(&users[1], &today, 0);
+    let serials = [
+        present(&users[0], &today, 1).0.serial(),
+        present(&users[0], &today, 2).0.serial(),
+        present(&users[0], &tomorrow, 0).0.serial(),
+        other_user.serial(),
+    ];
+    assert!(serials.iter().all(|s| *s != first.serial()));
+    assert!(users[0]
+        .make_k_times_presentation(&params, &public_keys, &today, 3, &challenge)
+        .is_none());
+
+    // The presentation only verifies for its own limit, period, challenge and serial
+    let stricter = UsageLimit::new(b"api.example", 20, 2);
+    assert!(!first.verify(&params, &public_keys, &accumulator, &tomorrow, &challenge));
+    assert!(!first.verify(&params, &public_keys, &accumulator, &stricter, &challenge));
+    assert!(!first.verify(
+        &params,
+        &public_keys,
+        &accumulator,
+        &today,
+        &[1u8; 2 * SECURITY_BYTES]
+    ));
+    let mut swapped = first.clone();
+    swapped.serial = other_user.serial;
+    assert!(!swapped.verify(&params, &public_keys, &accumulator, &today, &challenge));
+}
+
+// Tests that traceable presentations encrypt the user ID for the auditor to open
+#[test]
+fn test_traceable_presentations() {
+    let params = AccParams::default();
+    let (server, users) = setup(&params, 3);
+    let public_keys = server.get_public_keys();
+    let accumulator = server.get_accumulator();
+    let auditor = AuditorSecretKey::random();
+    let auditor_key = auditor.public_key();
+    let mut challenge = [0u8; 2 * SECURITY_BYTES];
+    rand::rngs::OsRng.fill_bytes(&mut challenge);
+
+    let presentation = users[1]
+        .make_traceable_presentation(&params, &public_keys, &auditor_key, &challenge)
+        .unwrap();
+    assert!(presentation.verify(
+        &params,
+        &public_keys,
+        &accumulator,
+        &auditor_key,
+        &challenge
+    ));
+    let other_auditor = AuditorSecretKey::random().public_key();
+    assert!(!presentation.verify(
+        &params,
+        &public_keys,
+        &accumulator,
+        &other_auditor,
+        &challenge
+    ));
+
+    // The ciphertext cannot be swapped for an encryption of another ID
+    let mut swapped = presentation;
+    swapped.ciphertext.e_2 += G1Projective::GENERATOR;
+    assert!(!swapped.verify(
+        &params,
+        &public_keys,
+        &accumulator,
+        &auditor_key,
+        &challenge
+    ));
+
+    // The auditor recovers the user with a checkable opening
+    let ciphertext = presentation.ciphertext();
+    let opening = auditor.open(&ciphertext);
+    assert!(opening.verify(&auditor_key, &ciphertext));
+    let ids: Vec<UserID> = users.iter().map(|u| u.get_id()).collect();
+    assert_eq!(opening.identify(&ids), Some(users[1].get_id()));
+    assert!(!opening.is_user(&users[0].get_id()));
+    assert!(!opening.verify(&auditor_key, &swapped.ciphertext));
+    let wrong = AuditorSecretKey::random().open(&ciphertext);
+    assert!(!wrong.verify(&auditor_key, &ciphertext));
+}
+
+// Tests that designated-verifier proofs convince only their verifier, who can simulate them
+#[test]
+fn test_designated_verifier_proofs() {
+    let params = AccParams::default();
+    let (server, mut users) = setup(&params, 1);
+    let user = users.pop().unwrap();
+    let public_keys = server.get_public_keys();
+    let accumulator = server.get_accumulator();
+    let verifier = DesignatedVerifierKey::random();
+    let verifier_key = verifier.public_key();
+    let other_key = DesignatedVerifierKey::random().public_key();
+    let mut challenge = [0u8; 2 * SECURITY_BYTES];
+    rand::rngs::OsRng.fill_bytes(&mut challenge);
+
+    let proof = user
+        .make_designated_membership_proof(&params, &public_keys, &verifier_key, &challenge)
+        .unwrap();
+    assert!(proof.verify(
+        &params,
+        &public_keys,
+        &accumulator,
+        &verifier_key,
+        &challenge
+    ));
+    assert!(!proof.verify(&params, &public_keys, &accumulator, &other_key, &challenge));
+    assert!(!Witness::check_membership_proof(
+        &proof.proof,
+        &params,
+        &public_keys,
+        &accumulator,
+        &challenge
+    ));
+    let mut tampered = proof;
+    tampered.c_v += Scalar::ONE;
+    assert!(!tampered.verify(
+        &params,
+        &public_keys,
+        &accumulator,
+        &verifier_key,
+        &challenge
+    ));
+
+    // The verifier can make proofs that verify without any member,
+    // so a proof it shows to a third party proves nothing
+    let statement = Statement {
+        public_keys: &public_keys,
+        accumulator: &accumulator,
+        epoch: server.get_epoch(),
+    };
+    let simulated = verifier.simulate(&statement, &params, &challenge);
+    assert!(simulated.verify(
+        &params,
+        &public_keys,
+        &accumulator,
+        &verifier_key,
+        &challenge
+    ));
+    assert!(!simulated.verify(&params, &public_keys, &accumulator, &other_key, &challenge));
+}
+
+// Tests that attribute presentations disclose only the chosen attributes
+// and that credentials cannot be combined into new ones
+#[test]
+fn test_attribute_credentials() {
+    let params = AccParams::default();
+    let mut server = Server::new(&params);
+    let mut user = User::new(&server, UserID::random(), &[server.get_epoch_public_key()]).unwrap();
+    let role = Element::hash(b"admin");
+    let region = Element::hash(b"eu");
+    let tier = Element(Scalar::from(3u64));
+    assert!(user
+        .create_credential(&params, &server, &[role, region, tier])
+        .is_err());
+    server.add(user.get_id());
+    user.create_witness(&params, &server);
+    user.create_credential(&params, &server, &[role, region, tier])
+        .unwrap();
+    let public_keys = server.get_public_keys();
+    let accumulator = server.get_accumulator();
+    let credential = user.credential.clone().unwrap();
+    let witness = user.witness.clone().unwrap();
+    assert!(credential
+        .verify(&witness, &public_keys, &params, &user.get_id())
+        .is_ok());
+    let mut challenge = [0u8; 2 * SECURITY_BYTES];
+    rand::rngs::OsRng.fill_bytes(&mut challenge);
+
+    // Reveal only the role; the region and tier stay hidden
+    let presentation = user
+        .make_attribute_presentation(&params, &public_keys, &[0], &challenge)
+        .unwrap();
+    assert!(presentation.verify(&params, &public_keys, &accumulator, &challenge));
+    assert_eq!(presentation.disclosed(), &[(0, role)]);
+    assert_eq!(presentation.num_attributes(), 3);
+    assert!(!presentation.verify(
+        &params,
+        &public_keys,
+        &accumulator,
+        &[0u8; 2 * SECURITY_BYTES]
+    ));
+    let mut forged = presentation.clone();
+    forged.disclosed[0].1 = Element::hash(b"root");
+    assert!(!forged.verify(&params, &public_keys, &accumulator, &challenge));
+    let everything = user
+        .make_attribute_presentation(&params, &public_keys, &[2, 0, 1], &challenge)
+        .unwrap();
+    assert!(everything.verify(&params, &public_keys, &accumulator, &challenge));
+    assert_eq!(everything.disclosed(), &[(0, role), (1, region), (2, tier)]);
+    assert!(user
+        .make_attribute_presentation(&params, &public_keys, &[3], &challenge)
+        .is_none());
+
+    // Every signature has a fresh e and s, so two credentials cannot be combined:
+    // A + k(A' - A) does not sign the tier 3 + k(4 - 3) = 10
+    user.create_credential(
+        &params,
+        &server,
+        &[role, region, Element(Scalar::from(4u64))],
+    )
+    .unwrap();
+    let other = user.credential.clone().unwrap();
+    assert_ne!(other.e, credential.e);
+    assert_ne!(other.s, credential.s);
+    let k = Scalar::from(7u64);
+    let forged = Credential {
+        attributes: vec![role, region, Element(Scalar::from(10u64))],
+        signature: credential.signature + (other.signature - credential.signature) * k,
+        expiry: credential.expiry,
+        e: credential.e,
+        s: credential.s + (other.s - credential.s) * k,
+    };
+    assert!(forged
+        .verify(&witness, &public_keys, &params, &user.get_id())
+        .is_err());
+    let mut forger = user.clone();
+    forger.credential = Some(forged);
+    assert!(forger
+        .make_attribute_presentation(&params, &public_keys, &[2], &challenge)
+        .is_none());
+
+    // Deleting the user revokes the credential with the witness
+    server.delete(user.get_id());
+    let new_accumulator = server.get_accumulator();
+    assert!(!presentation.verify(&params, &public_keys, &new_accumulator, &challenge));
+}
+
+// Tests that expiry presentations prove the expiry the server signed is later
+// than the verifier's epoch, without revealing it
+#[test]
+fn test_expiry_presentations() {
+    let params = AccParams::default();
+    let (mut server, mut users) = setup(&params, 1);
+    let mut user = users.pop().unwrap();
+    let public_keys = server.get_public_keys();
+    let accumulator = server.get_accumulator();
+    let epoch = server.get_epoch();
+    let role = Element::hash(b"member");
+    let mut challenge = [0u8; 2 * SECURITY_BYTES];
+    rand::rngs::OsRng.fill_bytes(&mut challenge);
+
+    // The witness comes with a credential the server set to expire
+    let credential = user.credential.clone().unwrap();
+    assert_eq!(credential.expiry, (epoch + CREDENTIAL_LIFETIME) as u64);
+    assert!(user
+        .make_expiry_presentation(&params, &public_keys, &challenge)
+        .unwrap()
+        .verify(&params, &public_keys, &accumulator, epoch, &challenge));
+
+    server.credential_lifetime = 5;
+    user.create_credential(&params, &server, &[role]).unwrap();
+    assert_eq!(user.credential.as_ref().unwrap().expiry, epoch as u64 + 5);
+    let presentation = user
+        .make_expiry_presentation(&params, &public_keys, &challenge)
+        .unwrap();
+    assert!(presentation.verify(&params, &public_keys, &accumulator, epoch, &challenge));
+    assert_eq!(presentation.epoch(), epoch);
+    assert!(presentation.presentation().disclosed().is_empty());
+    assert!(!presentation.verify(
+        &params,
+        &public_keys,
+        &accumulator,
+        epoch,
+        &[0u8; 2 * SECURITY_BYTES]
+    ));
+    let mut tampered = presentation.clone();
+    tampered.bit_challenges[0] += Scalar::ONE;
+    assert!(!tampered.verify(&params, &public_keys, &accumulator, epoch, &challenge));
+    let mut tampered = presentation.clone();
+    tampered.bit_commitments.swap(0, 1);
+    assert!(!tampered.verify(&params, &public_keys, &accumulator, epoch, &challenge));
+
+    // A proof for one epoch does not pass at another, even with its epoch rewritten
+    assert!(!presentation.verify(&params, &public_keys, &accumulator, epoch + 1, &challenge));
+    assert!(!presentation.verify(&params, &public_keys, &accumulator, epoch - 1, &challenge));
+    let mut forged = presentation.clone();
+    forged.presentation.proof.epoch = epoch + 4;
+    assert!(!forged.verify(&params, &public_keys, &accumulator, epoch + 4, &challenge));
+
+    // The user cannot move the expiry the server signed
+    let mut extended = user.credential.clone().unwrap();
+    extended.expiry += 100;
+    let witness = user.witness.clone().unwrap();
+    assert!(extended
+        .verify(&witness, &public_keys, &params, &user.get_id())
+        .is_err());
+    let mut forger = user.clone();
+    forger.credential = Some(extended);
+    assert!(forger
+        .make_expiry_presentation(&params, &public_keys, &challenge)
+        .is_none());
+
+    // A credential expiring at the current epoch has expired
+    server.credential_lifetime = 0;
+    user.create_credential(&params, &server, &[role]).unwrap();
+    assert!(user
+        .make_expiry_presentation(&params, &public_keys, &challenge)
+        .is_none());
+    server.credential_lifetime = 1;
+    user.create_credential(&params, &server, &[role]).unwrap();
+    let last = user
+        .make_expiry_presentation(&params, &public_keys, &challenge)
+        .unwrap();
+    assert!(last.verify(&params, &public_keys, &accumulator, epoch, &challenge));
+}
+
+// Tests that joint proofs show membership under several issuers for the same user ID
+#[test]
+fn test_joint_membership_proofs() {
+    let params = AccParams::default();
+    let mut servers = [Server::new(&params), Server::new(&params)];
+    let id = UserID::random();
+    let mut users = Vec::new();
+    for server in servers.iter_mut() {
+        let mut user = User::new(server, id, &[server.get_epoch_public_key()]).unwrap();
+        server.add(id);
+        user.create_witness(&params, server);
+        users.push(user);
+    }
+    let keys: Vec<(PublicKeys, Accumulator)> = servers
+        .iter()
+        .map(|s| (s.get_public_keys(), s.get_accumulator()))
+        .collect();
+    let issuers: Vec<Statement> = keys
+        .iter()
+        .zip(&servers)
+        .map(|((public_keys, accumulator), s)| Statement {
+            public_keys,
+            accumulator,
+            epoch: s.get_epoch(),
+        })
+        .collect();
+    let mut challenge = [0u8; 2 * SECURITY_BYTES];
+    rand::rngs::OsRng.fill_bytes(&mut challenge);
+
+    let proof =
+        User::make_joint_membership_proof(&[&users[0], &users[1]], &params, &challenge).unwrap();
+    assert_eq!(proof.len(), 2);
+    assert!(proof.verify(&params, &issuers, &challenge));
+
+    // Every part must be for the epoch the verifier expects from its issuer
+    let mut stale = issuers.clone();
+    stale[1].epoch += 1;
+    assert!(!proof.verify(&params, &stale, &challenge));
+    let mut tampered = proof.clone();
+    tampered.proofs[1].epoch += 1;
+    assert!(!tampered.verify(&params, &issuers, &challenge));
+    assert!(!tampered.verify(&params, &stale, &challenge));
+    assert!(!proof.verify(&params, &[issuers[1], issuers[0]], &challenge));
+    assert!(!proof.verify(&params, &issuers[..1], &challenge));
+    assert!(!proof.verify(&params, &issuers, &[0u8; 2 * SECURITY_BYTES]));
+    let mut tampered = proof.clone();
+    tampered.proofs[1].s_7 += Scalar::ONE;
+    assert!(!tampered.verify(&params, &issuers, &challenge));
+
+    // A user with another ID under the second issuer cannot join the proof
+    let mut other = User::new(
+        &servers[1],
+        UserID::random(),
+        &[servers[1].get_epoch_public_key()],
+    )
+    .unwrap();
+    servers[1].add(other.get_id());
+    other.create_witness(&params, &servers[1]);
+    assert!(User::make_joint_membership_proof(&[&users[0], &other], &params, &challenge).is_none());
+    let forced = Witness::make_joint_membership_proof(
+        &[
+            IssuerWitness {
+                witness: users[0].witness.as_ref().unwrap(),
+                statement: Statement {
+                    public_keys: &users[0].public_keys,
+                    accumulator: &users[0].accumulator,
+                    epoch: users[0].epoch,
+                },
+            },
+            IssuerWitness {
+                witness: other.witness.as_ref().unwrap(),
+                statement: Statement {
+                    public_keys: &other.public_keys,
+                    accumulator: &other.accumulator,
+                    epoch: other.epoch,
+                },
+            },
+        ],
+        &id,
+        &params,
+        &challenge,
+    );
+    assert!(forced.is_none());
+}
+
+// Tests that Pedersen membership proofs commit to the proven user ID
+#[test]
+fn test_pedersen_membership_proofs() {
+    let params = AccParams::default();
+    let (server, mut users) = setup(&params, 1);
+    let user = users.pop().unwrap();
+    let public_keys = server.get_public_keys();
+    let accumulator = server.get_accumulator();
+    let generators = PedersenGenerators::default();
+    let mut challenge = [0u8; 2 * SECURITY_BYTES];
+    rand::rngs::OsRng.fill_bytes(&mut challenge);
+
+    let (proof, r) = user
+        .make_pedersen_membership_proof(&params, &public_keys, &generators, &challenge)
+        .unwrap();
+    assert!(proof.verify(&params, &public_keys, &accumulator, &generators, &challenge));
+    // The user can open the commitment to the accumulated ID in an external proof
+    assert_eq!(
+        proof.commitment(),
+        generators.g * user.get_id().0 + generators.h * r
+    );
+
+    // The commitment is bound to the proof and to the generators
+    let other = PedersenGenerators {
+        g: generators.h,
+        h: generators.g,
+    };
+    assert!(!proof.verify(&params, &public_keys, &accumulator, &other, &challenge));
+    let mut moved = proof;
+    moved.commitment += generators.g;
+    assert!(!moved.verify(&params, &public_keys, &accumulator, &generators, &challenge));
+    assert!(!proof.verify(
+        &params,
+        &public_keys,
+        &accumulator,
+        &generators,
+        &[0u8; 2 * SECURITY_BYTES]
+    ));
+}
@@ -0,0 +1,206 @@
+use crate::accumulator::{generate_fr, schnorr, Accumulator, Element, SALT};
+use crate::utils::{AccParams, PublicKeys, UserID, SECURITY_BYTES};
+use crate::witness::{MembershipProof, Statement, Witness};
+use blsful::inner_types::*;
+use merlin::Transcript;
+use serde::{Deserialize, Serialize};
+
+/// The key of an auditor who can de-anonymize traceable presentations
+#[derive(Clone, Deserialize, Serialize)]
+pub struct AuditorSecretKey(pub Scalar);
+
+/// The public key presentations are encrypted to, A = aG
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+pub struct AuditorPublicKey(pub G1Projective);
+
+impl AuditorSecretKey {
+    /// Generates a random auditor key
+    pub fn random() -> Self {
+        Self(generate_fr(SALT, None, rand::rngs::OsRng))
+    }
+
+    /// The matching public key
+    pub fn public_key(&self) -> AuditorPublicKey {
+        AuditorPublicKey(G1Projective::GENERATOR * self.0)
+    }
+
+    /// Decrypts the user ID point yG of a presentation,
+    /// with a proof that the decryption is correct
+    pub fn open(&self, ciphertext: &UserCiphertext) -> Opening {
+        let point = ciphertext.e_2 - ciphertext.e_1 * self.0;
+        // Shows log_G(A) = log_{E_1}(E_2 - M)
+        let k = generate_fr(SALT, None, rand::rngs::OsRng);
+        let mut transcript = opening_transcript(&self.public_key(), ciphertext, &point);
+        transcript.append_message(b"T_1", &(G1Projective::GENERATOR * k).to_compressed());
+        transcript.append_message(b"T_2", &(ciphertext.e_1 * k).to_compressed());
+        let challenge = Element::from_transcript(b"challenge", &mut transcript).0;
+        Opening {
+            point,
+            challenge,
+            response: schnorr(k, self.0, challenge),
+        }
+    }
+}
+
+/// An ElGamal encryption (E_1, E_2) = (rG, yG + rA) of a user ID under an auditor key
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+pub struct UserCiphertext {
+    /// E_1 = rG
+    pub e_1: G1Projective,
+    /// E_2 = yG + rA
+    pub e_2: G1Projective,
+}
+
+/// An auditor's decryption of a `UserCiphertext`, which anyone can check
+#[derive(Copy, Clone, Debug, Deserialize, Serialize)]
+pub struct Opening {
+    point: G1Projective,
+    challenge: Scalar,
+    response: Scalar,
+}
+
+impl Opening {
+    /// Checks that the opening is the correct decryption of `ciphertext`
+    pub fn verify(&self, auditor_key: &AuditorPublicKey, ciphertext: &UserCiphertext) -> bool {
+        // T_1 = sG - cA
+        let t_1 = G1Projective::GENERATOR * self.response - auditor_key.0 * self.challenge;
+        // T_2 = sE_1 - c(E_2 - M)
+        let t_2 = ciphertext.e_1 * self.response - (ciphertext.e_2 - self.point) * self.challenge;
+        let mut transcript = opening_transcript(auditor_key, ciphertext, &self.point);
+        transcript.append_message(b"T_1", &t_1.to_compressed());
+        transcript.append_message(b"T_2", &t_2.to_compressed());
+        Element::from_transcript(b"challenge", &mut transcript).0 == self.challenge
+    }
+
+    /// Whether the opening decrypts to `user_id`
+    pub fn is_user(&self, user_id: &UserID) -> bool {
+        self.point == G1Projective::GENERATOR * user_id.0
+    }
+
+    /// Finds the user among the candidates the opening decrypts to
+    pub fn identify(&self, candidates: &[UserID]) -> Option<UserID> {
+        candidates.iter().copied().find(|y| self.is_user(y))
+    }
+}
+
+/// A membership proof that also encrypts the user's ID to an auditor.
+/// The encryption is proved with the blinding of s_7,
+/// so it holds the same y as the membership proof.
+/// Verifiers learn nothing about y; only the auditor can open it
+#[derive(Copy, Clone, Debug, Deserialize, Serialize)]
+pub struct TraceablePresentation {
+    pub(crate) proof: MembershipProof,
+    pub(crate) ciphertext: UserCiphertext,
+    pub(crate) s_r: Scalar,
+}
+
+impl TraceablePresentation {
+    /// The underlying membership proof
+    pub fn proof(&self) -> MembershipProof {
+        self.proof
+    }
+
+    /// The encrypted user ID, for the auditor to open
+    pub fn ciphertext(&self) -> UserCiphertext {
+        self.ciphertext
+    }
+
+    /// Verifies the presentation and its encryption to `auditor_key`
+    pub fn verify(
+        &self,
+        params: &AccParams,
+        public_keys: &PublicKeys,
+        accumulator: &Accumulator,
+        auditor_key: &AuditorPublicKey,
+        ephemeral_challenge: &[u8; 2 * SECURITY_BYTES],
+    ) -> bool {
+        let c = self.proof.challenge;
+        // T_E1 = s_rG - cE_1
+        let t_e1 = G1Projective::GENERATOR * self.s_r - self.ciphertext.e_1 * c;
+        // T_E2 = s_7G + s_rA - cE_2
+        let t_e2 = G1Projective::GENERATOR * self.proof.s_7 + auditor_key.0 * self.s_r
+            - self.ciphertext.e_2 * c;
+        Witness::check_bound(
+            &self.proof,
+            params,
+            public_keys,
+            accumulator,
+            |transcript| {
+                bind(
+                    transcript,
+                    auditor_key,
+                    &self.ciphertext,
+                    [t_e1, t_e2],
+                    ephemeral_challenge,
+                )
+            },
+        )
+    }
+}
+
+impl Witness {
+    /// Constructs a membership proof with the user ID encrypted to `auditor_key`
+    pub fn make_traceable_presentation(
+        witness: &Witness,
+        user_id: &UserID,
+        statement: &Statement,
+        params: &AccParams,
+        auditor_key: &AuditorPublicKey,
+        ephemeral_challenge: &[u8; 2 * SECURITY_BYTES],
+    ) -> Option<TraceablePresentation> {
+        let rng = rand::rngs::OsRng;
+        let r = generate_fr(SALT, None, rng);
+        let k_r = generate_fr(SALT, None, rng);
+        let ciphertext = UserCiphertext {
+            e_1: G1Projective::GENERATOR * r,
+            e_2: G1Projective::GENERATOR * user_id.0 + auditor_key.0 * r,
+        };
+        let proof = Self::prove_bound(witness, user_id, statement, params, |transcript, k| {
+            // T_E1 = k_rG, T_E2 = k_7G + k_rA
+            let t_e1 = G1Projective::GENERATOR * k_r;
+            let t_e2 = G1Projective::GENERATOR * k[7] + auditor_key.0 * k_r;
+            bind(
+                transcript,
+                auditor_key,
+                &ciphertext,
+                [t_e1, t_e2],
+                ephemeral_challenge,
+            )
+        })?;
+        Some(TraceablePresentation {
+            proof,
+            ciphertext,
+            s_r: schnorr(k_r, r, proof.challenge),
+        })
+    }
+}
+
+// Adds the auditor key, the ciphertext and its commitments to the proof transcript
+fn bind(
+    transcript: &mut Transcript,
+    auditor_key: &AuditorPublicKey,
+    ciphertext: &UserCiphertext,
+    commitments: [G1Projective; 2],
+    ephemeral_challenge: &[u8; 2 * SECURITY_BYTES],
+) {
+    transcript.append_message(b"Auditor key", &auditor_key.0.to_compressed());
+    transcript.append_message(b"E_1", &ciphertext.e_1.to_compressed());
+    transcript.append_message(b"E_2", &ciphertext.e_2.to_compressed());
+    transcript.append_message(b"T_E1", &commitments[0].to_compressed());
+    transcript.append_message(b"T_E2", &commitments[1].to_compressed());
+    transcript.append_message(b"Ephemeral challenge", ephemeral_challenge);
+}
+
+// Starts the transcript of a proof of correct decryption
+fn opening_transcript(
+    auditor_key: &AuditorPublicKey,
+    ciphertext: &UserCiphertext,
+    point: &G1Projective,
+) -> Transcript {
+    let mut transcript = Transcript::new(b"auditor_opening_proof");
+    transcript.append_message(b"Auditor key", &auditor_key.0.to_compressed());
+    transcript.append_message(b"E_1", &ciphertext.e_1.to_compressed());
+    transcript.append_message(b"E_2", &ciphertext.e_2.to_compressed());
+    transcript.append_message(b"M", &point.to_compressed());
+    transcript
+}
@@ -0,0 +1,224 @@
+use crate::accumulator::{Element, SecretKey};
+use crate::servers::ServerUpdate;
+use crate::user::UserUpdate;
+use crate::utils::{sc, usize_from};
+use blsful::inner_types::*;
+use chacha20poly1305::{
+    aead::{AeadInPlace, KeyInit},
+    ChaCha20Poly1305, Key, Nonce, Tag,
+};
+use serde::{Deserialize, Serialize};
+use sha3::{
+    digest::{ExtendableOutput, Update, XofReader},
+    Shake256,
+};
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+/// Domain separation for the transport key
+const TRANSPORT_DST: &[u8] = b"ALLOSAUR_SEALED_UPDATE";
+/// The number of bytes in an authentication tag
+const TAG_BYTES: usize = 16;
+
+/// The key a server publishes for users to seal update requests to.
+/// Every server has its own, next to the accumulator's `PublicKeys`
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+pub struct EncryptionPublicKey(pub G1Projective);
+
+/// A user update request for a single server
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct UpdateRequest {
+    /// The epoch the user wants to update to
+    pub epoch: usize,
+    /// The number of epochs to update over
+    pub epoch_diff: usize,
+    /// This server's shares of the powers of y
+    pub y_shares: Vec<Scalar>,
+}
+
+impl UpdateRequest {
+    /// Get the byte representation of the request
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut res = Vec::with_capacity(16 + 32 * self.y_shares.len());
+        res.extend_from_slice(&(self.epoch as u64).to_be_bytes());
+        res.extend_from_slice(&(self.epoch_diff as u64).to_be_bytes());
+        for s in &self.y_shares {
+            res.extend_from_slice(&s.to_be_bytes());
+        }
+        res
+    }
+
+    /// Convert a byte representation to a request
+    pub fn from_bytes(input: &[u8]) -> Result<Self, &'static str> {
+        if input.len() < 16 || (input.len() - 16) % 32 != 0 {
+            return Err("invalid request length");
+        }
+        let epoch = usize_from(&input[0..8])?;
+        let epoch_diff = usize_from(&input[8..16])?;
+        let y_shares = input[16..]
+            .chunks(32)
+            .map(sc)
+            .collect::<Result<Vec<Scalar>, &'static str>>()?;
+        Ok(Self {
+            epoch,
+            epoch_diff,
+            y_shares,
+        })
+    }
+
+    /// Encrypts this request to a server's encryption key.
+    /// Returns the sealed request and the key to open the server's response
+    pub fn seal(&self, server_key: &EncryptionPublicKey) -> (SealedRequest, ResponseKey) {
+        let server_key = &server_key.0;
+        let ephemeral_secret = Element::random().0;
+        let ephemeral = G1Projective::GENERATOR * ephemeral_secret;
+        let shared = *server_key * ephemeral_secret;
+        let keys = TransportKeys::derive(&ephemeral, server_key, &shared);
+        let (ciphertext, tag) = keys.seal(REQUEST, &self.to_bytes());
+        (
+            SealedRequest {
+                ephemeral,
+                ciphertext,
+                tag,
+            },
+            ResponseKey(keys),
+        )
+    }
+}
+
+impl UserUpdate {
+    /// Seals each server's shares to that server's encryption key,
+    /// so that a relay in front of the servers learns nothing
+    /// about the user's ID.
+    /// The i-th request and response key belong to the i-th server
+    pub fn seal(
+        &self,
+        epoch: usize,
+        server_keys: &[EncryptionPublicKey],
+    ) -> Result<(Vec<SealedRequest>, Vec<ResponseKey>), &'static str> {
+        if server_keys.len() != self.y_shares.len() {
+            return Err("one key per server is required");
+        }
+        Ok(server_keys
+            .iter()
+            .zip(self.y_shares.iter())
+            .map(|(key, shares)| {
+                UpdateRequest {
+                    epoch,
+                    epoch_diff: self.epoch_diff,
+                    y_shares: shares.clone(),
+                }
+                .seal(key)
+            })
+            .unzip())
+    }
+}
+
+/// An update request encrypted to one server
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SealedRequest {
+    /// The user's ephemeral public key
+    pub ephemeral: G1Projective,
+    /// The encrypted request
+    pub ciphertext: Vec<u8>,
+    /// The authentication tag of the ciphertext
+    pub tag: [u8; TAG_BYTES],
+}
+
+impl SealedRequest {
+    /// Decrypts the request with the server's encryption secret key.
+    /// Returns the request and the key to seal the response with
+    pub fn open(
+        &self,
+        secret_key: &SecretKey,
+    ) -> Result<(UpdateRequest, ResponseKey), &'static str> {
+        let server_key = G1Projective::GENERATOR * secret_key.0;
+        let shared = self.ephemeral * secret_key.0;
+        let keys = TransportKeys::derive(&self.ephemeral, &server_key, &shared);
+        let plaintext = keys.open(REQUEST, &self.ciphertext, &self.tag)?;
+        Ok((UpdateRequest::from_bytes(&plaintext)?, ResponseKey(keys)))
+    }
+}
+
+/// A server response encrypted back to the user
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SealedResponse {
+    /// The encrypted response
+    pub ciphertext: Vec<u8>,
+    /// The authentication tag of the ciphertext
+    pub tag: [u8; TAG_BYTES],
+}
+
+/// The key shared by a user and one server for a single request and response
+#[derive(Clone, Zeroize, ZeroizeOnDrop)]
+pub struct ResponseKey(TransportKeys);
+
+impl ResponseKey {
+    /// Encrypts a server's response
+    pub fn seal(&self, response: &ServerUpdate) -> SealedResponse {
+        let (ciphertext, tag) = self.0.seal(RESPONSE, &response.to_bytes());
+        SealedResponse { ciphertext, tag }
+    }
+
+    /// Decrypts a server's response
+    pub fn open(&self, response: &SealedResponse) -> Result<ServerUpdate, &'static str> {
+        let plaintext = self.0.open(RESPONSE, &response.ciphertext, &response.tag)?;
+        ServerUpdate::from_bytes(&plaintext)
+    }
+}
+
+// The nonces of the request and the response.
+// Every request derives a fresh key, so each nonce is used once per key
+const REQUEST: u8 = 0;
+const RESPONSE: u8 = 1;
+
+// ECIES-style key for ChaCha20-Poly1305, derived from the ephemeral
+// Diffie-Hellman secret with SHAKE256
+#[derive(Clone, Zeroize, ZeroizeOnDrop)]
+struct TransportKeys {
+    key: [u8; 32],
+}
+
+impl TransportKeys {
+    fn derive(ephemeral: &G1Projective, server_key: &G1Projective, shared: &G1Projective) -> Self {
+        let mut hasher = Shake256::default();
+        hasher.update(TRANSPORT_DST);
+        hasher.update(&ephemeral.to_compressed());
+        hasher.update(&server_key.to_compressed());
+        hasher.update(&shared.to_compressed());
+        let mut key = [0u8; 32];
+        hasher.finalize_xof().read(&mut key);
+        Self { key }
+    }
+
+    fn seal(&self, direction: u8, plaintext: &[u8]) -> (Vec<u8>, [u8; TAG_BYTES]) {
+        let mut ciphertext = plaintext.to_vec();
+        let tag = self
+            .cipher()
+            .encrypt_in_place_detached(&nonce(direction), &[], &mut ciphertext)
+            .expect("a message shorter than the ChaCha20 limit");
+        (ciphertext, tag.into())
+    }
+
+    fn open(
+        &self,
+        direction: u8,
+        ciphertext: &[u8],
+        tag: &[u8; TAG_BYTES],
+    ) -> Result<Vec<u8>, &'static str> {
+        let mut plaintext = ciphertext.to_vec();
+        self.cipher()
+            .decrypt_in_place_detached(&nonce(direction), &[], &mut plaintext, Tag::from_slice(tag))
+            .map_err(|_| "invalid ciphertext")?;
+        Ok(plaintext)
+    }
+
+    fn cipher(&self) -> ChaCha20Poly1305 {
+        ChaCha20Poly1305::new(Key::from_slice(&self.key))
+    }
+}
+
+fn nonce(direction: u8) -> Nonce {
+    let mut nonce = Nonce::default();
+    nonce[11] = direction;
+    nonce
+}
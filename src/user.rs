@@ -3,7 +3,25 @@ use blsful::inner_types::*;
 use merlin::Transcript;
 use serde::{Deserialize, Serialize};
 
-use super::{servers::Server, utils::*, witness::*};
+use super::{
+    credential::{AttributePresentation, Credential},
+    designated::{DesignatedProof, DesignatedVerifierPublicKey},
+    epoch::{EpochPublicKey, SignedEpochHead},
+    expiry::ExpiryPresentation,
+    joint::{IssuerWitness, JointMembershipProof},
+    k_times::{KTimesPresentation, UsageLimit},
+    non_membership::{NonMembershipProof, NonWitness},
+    pedersen::{PedersenGenerators, PedersenMembershipProof},
+    policy::{UpdateCost, UpdatePolicy},
+    presentation::PresentationMessage,
+    range_update::RangeUpdate,
+    receipt::DeletionReceipt,
+    scoped::ScopedMembershipProof,
+    servers::{Server, ServerUpdate},
+    traceable::{AuditorPublicKey, TraceablePresentation},
+    utils::*,
+    witness::*,
+};
 
 /// The data a user needs to track
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -18,18 +36,58 @@ pub struct User {
     pub public_keys: PublicKeys,
     /// the epoch when the witness was last known to be valid
     pub epoch: usize,
+    /// How to choose the size of update messages
+    #[serde(default)]
+    pub update_policy: UpdatePolicy,
+    /// If set, updates always start from a multiple of this many epochs
+    /// to hide how long ago the user last updated
+    pub epoch_bucket: Option<usize>,
+    /// The witness at the last bucket boundary
+    pub checkpoint: Option<WitnessCheckpoint>,
+    /// Wrapper type for (x, (C, d), R_m) with its own epoch
+    pub non_membership: Option<NonWitness>,
+    /// A signature over y, x and attributes, used with `witness`
+    pub credential: Option<Credential>,
+    /// The epoch keys of the servers the user trusts, obtained out of band.
+    /// Heads are only accepted if signed under these keys
+    #[serde(default)]
+    pub epoch_keys: Vec<EpochPublicKey>,
+}
+
+/// A membership witness kept at an epoch bucket boundary
+#[derive(Copy, Clone, Debug, Deserialize, Serialize)]
+pub struct WitnessCheckpoint {
+    /// The epoch of the boundary
+    pub epoch: usize,
+    /// The membership witness at that epoch
+    pub witness: MembershipWitness,
+    /// The accumulator at that epoch
+    pub accumulator: Accumulator,
 }
 
 impl User {
-    /// New "empty" user
-    pub fn new(server: &Server, id: UserID) -> User {
-        User {
+    /// New "empty" user who pins the servers' `epoch_keys`.
+    /// Fails if the server's latest head is not signed under a pinned key
+    pub fn new(
+        server: &Server,
+        id: UserID,
+        epoch_keys: &[EpochPublicKey],
+    ) -> Result<User, &'static str> {
+        let head = server.get_head();
+        let accumulator = pinned_accumulator(&head, epoch_keys)?;
+        Ok(User {
             id,
             witness: None,
-            accumulator: server.get_accumulator(),
+            accumulator,
             public_keys: server.get_public_keys(),
-            epoch: server.get_epoch(),
-        }
+            epoch: head.head.epoch,
+            update_policy: UpdatePolicy::default(),
+            epoch_bucket: None,
+            checkpoint: None,
+            non_membership: None,
+            credential: None,
+            epoch_keys: epoch_keys.to_vec(),
+        })
     }
 
     /// Generates a random user and uses the secret keys provided
@@ -62,6 +120,12 @@ impl User {
             accumulator,
             public_keys,
             epoch,
+            update_policy: UpdatePolicy::default(),
+            epoch_bucket: None,
+            checkpoint: None,
+            non_membership: None,
+            credential: None,
+            epoch_keys: Vec::new(),
         }
     }
 
@@ -78,9 +142,117 @@ impl User {
     /// Creates a new witness for the user
     /// by generating a random new secret key,
     /// create a ZKPoK of this key, and ask the server given
-    /// as an argument for a new witness and long-term signature
+    /// as an argument for a new witness, long-term signature and credential.
+    /// The witness is only kept if it verifies against the accumulator
+    /// of the server's latest head, signed under a pinned key
     pub fn create_witness(&mut self, params: &AccParams, server: &Server) {
+        let (key, user_pub_key, challenge, response) = Self::prove_new_key(params);
+        // Send Schnorr proof and ID to server
+        if let Some((witness, signature, credential)) =
+            server.witness(params, &self.id, &challenge, &response, &user_pub_key)
+        {
+            let head = server.get_head();
+            let accumulator = match pinned_accumulator(&head, &self.epoch_keys) {
+                Ok(accumulator) => accumulator,
+                Err(_) => return,
+            };
+            let witness = Witness {
+                secret_key: key,
+                witness,
+                signature,
+            };
+            if Witness::verify(&accumulator, &self.public_keys, params, &self.id, &witness).is_ok()
+                && credential
+                    .verify(&witness, &self.public_keys, params, &self.id)
+                    .is_ok()
+            {
+                self.witness = Some(witness);
+                self.credential = Some(credential);
+                self.epoch = head.head.epoch;
+                self.accumulator = accumulator;
+                self.checkpoint = None;
+            }
+        }
+    }
+
+    /// Creates a new non-membership witness for the user
+    /// the same way as `create_witness`
+    pub fn create_non_membership_witness(&mut self, params: &AccParams, server: &Server) {
+        let (key, user_pub_key, challenge, response) = Self::prove_new_key(params);
+        if let Some((witness, signature)) =
+            server.non_membership_witness(params, &self.id, &challenge, &response, &user_pub_key)
+        {
+            let head = server.get_head();
+            let first = match server.get_epoch_head(1) {
+                Some(first) => first,
+                None => return,
+            };
+            let (accumulator, initial_accumulator) = match (
+                pinned_accumulator(&head, &self.epoch_keys),
+                pinned_accumulator(&first, &self.epoch_keys),
+            ) {
+                (Ok(accumulator), Ok(initial_accumulator)) => (accumulator, initial_accumulator),
+                _ => return,
+            };
+            let witness = NonWitness {
+                secret_key: key,
+                witness,
+                signature,
+                epoch: head.head.epoch,
+                accumulator,
+                initial_accumulator,
+            };
+            if witness.verify(&self.public_keys, params, &self.id).is_ok() {
+                self.non_membership = Some(witness);
+            }
+        }
+    }
+
+    /// Asks the server for a credential over `attributes`,
+    /// signed for the same secret key as the user's witness
+    pub fn create_credential(
+        &mut self,
+        params: &AccParams,
+        server: &Server,
+        attributes: &[Element],
+    ) -> Result<(), &'static str> {
+        let key = match &self.witness {
+            None => return Err("no witness"),
+            Some(witness) => &witness.secret_key,
+        };
+        let (user_pub_key, challenge, response) = Self::prove_key(params, key);
+        let credential = server
+            .credential(
+                params,
+                &self.id,
+                &challenge,
+                &response,
+                &user_pub_key,
+                attributes,
+            )
+            .ok_or("not issued")?;
+        self.credential = Some(credential);
+        Ok(())
+    }
+
+    // Generates a random secret key and a Schnorr proof of it
+    fn prove_new_key(params: &AccParams) -> (SecretKey, G1Projective, Element, Element) {
         let key = SecretKey::new(None);
+        let (user_pub_key, challenge, response) = Self::prove_key(params, &key);
+        (key, user_pub_key, challenge, response)
+    }
+
+    // The user's accumulator and epoch under the issuer's `public_keys`
+    fn statement<'a>(&'a self, public_keys: &'a PublicKeys) -> Statement<'a> {
+        Statement {
+            public_keys,
+            accumulator: &self.accumulator,
+            epoch: self.epoch,
+        }
+    }
+
+    // Makes a Schnorr proof of a secret key
+    fn prove_key(params: &AccParams, key: &SecretKey) -> (G1Projective, Element, Element) {
         let user_pub_key = params.get_k1() * key.0;
         // Create a Schnorr proof
         let k = Element::random();
@@ -90,22 +262,26 @@ impl User {
         transcript.append_message(b"commitment", k_point.to_bytes().as_ref());
         let challenge = Element::from_transcript(b"challenge", &mut transcript);
         let response = k.0 - challenge.0 * key.0;
-        // Send Schnorr proof and ID to server
-        if let Some((witness, signature)) = server.witness(
-            params,
-            &self.id,
-            &challenge,
-            &Element(response),
-            &user_pub_key,
-        ) {
-            self.witness = Some(Witness {
-                secret_key: key,
-                witness,
-                signature,
-            });
-            self.epoch = server.get_epoch();
-            self.accumulator = server.get_accumulator();
+        (user_pub_key, challenge, Element(response))
+    }
+
+    /// Predicts the message sizes and operation counts of an update
+    /// to `new_epoch` under the user's update policy
+    pub fn predict_update(
+        &self,
+        new_epoch: usize,
+        num_servers: usize,
+        threshold: usize,
+    ) -> Result<UpdateCost, &'static str> {
+        if new_epoch <= self.epoch {
+            return Err("invalid epoch");
         }
+        UpdateCost::predict(
+            new_epoch - self.epoch,
+            num_servers,
+            threshold,
+            self.update_policy,
+        )
     }
 
     /// Prepares the secret shares that will be sent to each server
@@ -115,6 +291,19 @@ impl User {
         new_epoch: usize,
         num_servers: usize,
         threshold: usize,
+    ) -> Result<UserUpdate, &'static str> {
+        if new_epoch <= self.epoch {
+            return Err("invalid epoch");
+        }
+        self.prepare_shares(new_epoch - self.epoch, num_servers, threshold)
+    }
+
+    // Creates the shares of the powers of y for an update over d epochs
+    fn prepare_shares(
+        &self,
+        d: usize,
+        num_servers: usize,
+        threshold: usize,
     ) -> Result<UserUpdate, &'static str> {
         if num_servers < threshold {
             return Err("invalid threshold");
@@ -122,17 +311,7 @@ impl User {
         if threshold <= 1 {
             return Err("invalid threshold");
         }
-
-        let d = new_epoch - self.epoch;
-        let mut k = ((d as f64) * 2.5).sqrt() as usize;
-
-        // We expect 32*(k-1) bytes user->server
-        // and 80*ceil(d/k) bytes server->user
-        // We want these to balance, with preference for user->server since it saves
-        // the user elliptic curve computations
-        while 2 * k < 5 * (d + k - 1) / k {
-            k += 1;
-        }
+        let k = self.update_policy.choose_k(d)?;
 
         // Create y, y^2, ..,. y^k-1
         let mut y_power = self.id.0;
@@ -172,8 +351,24 @@ impl User {
         threshold: usize,
         y_shares: &[Vec<Scalar>],
         y_values: &[Scalar],
-        dvs: &[(Vec<Scalar>, Vec<G1Projective>)],
+        dvs: &[ServerUpdate],
     ) -> Result<MembershipWitness, &'static str> {
+        // All servers must answer for the same epoch
+        if dvs.len() < threshold || dvs.iter().any(|dv| dv.epoch != dvs[0].epoch) {
+            return Err("servers disagree on epoch");
+        }
+        // Every share must have an answer, and every answer the same number of chunks
+        let chunks = dvs[0].ds.len();
+        if y_shares.len() < threshold
+            || y_shares.len() > dvs.len()
+            || y_shares.len() > y_values.len()
+            || chunks == 0
+            || dvs
+                .iter()
+                .any(|dv| dv.ds.len() != chunks || dv.vs.len() != chunks)
+        {
+            return Err("servers disagree on update length");
+        }
         // d_chunks_shares is a vector of "chunks" of the polynomial d
         // such that d(x) = d[0] + d[1]*y^1 + d[2]*y^2 + ....
         // Since these chunks are returned as secret shares from the servers,
@@ -187,15 +382,15 @@ impl User {
             // d = vector of d polynomial chunks
             // w = vector of w polynomial chunks
             if d_chunks_shares.is_empty() {
-                d_chunks_shares = vec![Vec::new(); dvs[i].0.len()];
+                d_chunks_shares = vec![Vec::new(); dvs[i].ds.len()];
             };
-            for (ii, d) in dvs[i].0.iter().enumerate() {
+            for (ii, d) in dvs[i].ds.iter().enumerate() {
                 d_chunks_shares[ii].push((y_values[i], *d));
             }
             if v_chunks_shares.is_empty() {
-                v_chunks_shares = vec![Vec::new(); dvs[i].1.len()];
+                v_chunks_shares = vec![Vec::new(); dvs[i].vs.len()];
             };
-            for (ii, v) in dvs[i].1.iter().enumerate() {
+            for (ii, v) in dvs[i].vs.iter().enumerate() {
                 v_chunks_shares[ii].push((y_values[i], *v));
             }
         }
@@ -243,51 +438,263 @@ impl User {
         Ok(new_witness)
     }
 
-    /// Updates to the latest available epoch, from a set of servers
-    pub fn update(&mut self, servers: &[Server], threshold: usize) -> Result<(), &'static str> {
-        if self.witness.is_none() {
-            return Err("No witness");
+    /// Updates to the latest epoch available at a threshold of the servers.
+    /// The new witness is only kept if it verifies against the accumulator
+    /// a threshold of the servers signed heads for that epoch.
+    /// With an epoch bucket set, the update starts from the checkpoint
+    /// at the last bucket boundary, see `update_plan`
+    pub fn update(
+        &mut self,
+        params: &AccParams,
+        servers: &[Server],
+        threshold: usize,
+    ) -> Result<(), &'static str> {
+        let mut witness = match &self.witness {
+            Some(witness) => witness.clone(),
+            None => return Err("No witness"),
+        };
+
+        // Servers may be at different epochs, so target the highest
+        // epoch that at least a threshold of them have reached
+        let epochs: Vec<usize> = servers.iter().map(|s| s.get_epoch()).collect();
+        let new_epoch = threshold_epoch(&epochs, threshold)?;
+        if new_epoch <= self.epoch {
+            return Ok(());
+        }
+
+        let plan = self.update_plan(new_epoch);
+        let mut checkpoint = self.checkpoint;
+        if self.bucket().is_some() && checkpoint.is_none_or(|c| c.epoch != plan[0].0) {
+            checkpoint = Some(self.rewind(params, servers, threshold, plan[0].0, &witness)?);
+        }
+        if let Some(c) = checkpoint.filter(|c| c.epoch == plan[0].0) {
+            witness.witness = c.witness;
+        }
+        let mut accumulator = self.accumulator;
+        for (from_epoch, to_epoch) in plan {
+            let (new_witness, new_accumulator) =
+                self.update_from(params, servers, threshold, from_epoch, to_epoch, &witness)?;
+            if self.is_bucket_boundary(to_epoch) {
+                checkpoint = Some(WitnessCheckpoint {
+                    epoch: to_epoch,
+                    witness: new_witness.witness,
+                    accumulator: new_accumulator,
+                });
+            }
+            witness = new_witness;
+            accumulator = new_accumulator;
+        }
+        self.witness = Some(witness);
+        self.accumulator = accumulator;
+        self.epoch = new_epoch;
+        self.checkpoint = checkpoint;
+        Ok(())
+    }
+
+    /// The (from, to) epochs of the requests an update to `new_epoch` sends.
+    /// With an epoch bucket set, every request starts at a bucket boundary,
+    /// at or before the user's epoch, and spans bucket * 2^i epochs aligned
+    /// to its size, except for the last one that ends at `new_epoch`.
+    /// Servers thus only learn which bucket the user last updated in
+    pub fn update_plan(&self, new_epoch: usize) -> Vec<(usize, usize)> {
+        let mut from_epoch = self.epoch;
+        let mut plan = Vec::new();
+        if let Some(bucket) = self.bucket() {
+            from_epoch = match &self.checkpoint {
+                Some(checkpoint) if checkpoint.epoch <= self.epoch => checkpoint.epoch,
+                _ => bucket_boundary(self.epoch, bucket),
+            };
+            let last_boundary = bucket_boundary(new_epoch, bucket);
+            while from_epoch < last_boundary {
+                let mut span = bucket;
+                while (from_epoch - 1) % (2 * span) == 0 && from_epoch + 2 * span <= last_boundary {
+                    span *= 2;
+                }
+                plan.push((from_epoch, from_epoch + span));
+                from_epoch += span;
+            }
+        }
+        if new_epoch > from_epoch {
+            plan.push((from_epoch, new_epoch));
+        }
+        plan
+    }
+
+    fn bucket(&self) -> Option<usize> {
+        self.epoch_bucket.filter(|b| *b > 0)
+    }
+
+    fn is_bucket_boundary(&self, epoch: usize) -> bool {
+        self.bucket().is_some_and(|b| (epoch - 1) % b == 0)
+    }
+
+    // Rewinds `witness` from the user's epoch to the earlier bucket boundary
+    // `epoch` with the public deletion receipts of the bucket, as
+    // C = C'(y' - y) + V' for each deletion of y' from V to V'.
+    // The rewound accumulator must be the one a threshold of servers signed
+    fn rewind(
+        &self,
+        params: &AccParams,
+        servers: &[Server],
+        threshold: usize,
+        epoch: usize,
+        witness: &Witness,
+    ) -> Result<WitnessCheckpoint, &'static str> {
+        let bucket = self.bucket().ok_or("no epoch bucket")?;
+        let heads: Vec<SignedEpochHead> = servers
+            .iter()
+            .filter_map(|s| s.get_epoch_head(epoch))
+            .collect();
+        let accumulator =
+            SignedEpochHead::threshold_accumulator(&heads, &self.epoch_keys, epoch, threshold)?;
+        for server in servers {
+            // Fetch the whole bucket to not reveal the user's epoch within it
+            let receipts: Vec<DeletionReceipt> = (epoch + 1..epoch + bucket)
+                .map_while(|e| server.deletion_receipt(e))
+                .take_while(|r| r.epoch <= self.epoch)
+                .collect();
+            if receipts.len() != self.epoch - epoch
+                || DeletionReceipt::verify_chain(&receipts, params, &self.public_keys.witness_key)
+                    .is_err()
+                || receipts.last().map_or(accumulator, |r| r.accumulator) != self.accumulator
+                || receipts.first().map_or(accumulator, |r| r.previous) != accumulator
+            {
+                continue;
+            }
+            let mut rewound = witness.clone();
+            for receipt in receipts.iter().rev() {
+                rewound.witness = MembershipWitness(
+                    rewound.witness.0 * (receipt.deleted.0 - self.id.0) + receipt.accumulator.0,
+                );
+            }
+            if Witness::verify(&accumulator, &self.public_keys, params, &self.id, &rewound).is_ok()
+            {
+                return Ok(WitnessCheckpoint {
+                    epoch,
+                    witness: rewound.witness,
+                    accumulator,
+                });
+            }
         }
-        // // Check that current witness is valid
-        // let acc = server.get_accumulator();
-        // if !ver(self.accumulator, self.wit_public_key, self.sign_public_key, params, self.id, self.witness){
-        //     return;
-        // }
-        // If so, attempt update
+        Err("cannot rewind to the bucket boundary")
+    }
 
+    // Runs one threshold update of `witness` from `from_epoch` to `new_epoch`
+    fn update_from(
+        &self,
+        params: &AccParams,
+        servers: &[Server],
+        threshold: usize,
+        from_epoch: usize,
+        new_epoch: usize,
+        witness: &Witness,
+    ) -> Result<(Witness, Accumulator), &'static str> {
         // Precompute shares
-        let (d, y_shares, y_values) =
-            match self.prepare_for_update(servers[0].get_epoch(), servers.len(), threshold) {
-                Ok(UserUpdate {
-                    epoch_diff,
-                    y_shares,
-                    y_values,
-                }) => (epoch_diff, y_shares, y_values),
-                Err(e) => return Err(e),
-            };
+        let UserUpdate {
+            epoch_diff: d,
+            y_shares,
+            y_values,
+        } = self.prepare_shares(new_epoch - from_epoch, servers.len(), threshold)?;
         // Get answer from each server (directly)
-        let dvs: Vec<(Vec<Scalar>, Vec<G1Projective>)> = (0..servers.len())
-            .map(|i| servers[i].update(d, &y_shares[i]))
-            .collect();
+        // and keep only the servers that answered for the target epoch
+        let mut shares = Vec::with_capacity(servers.len());
+        let mut values = Vec::with_capacity(servers.len());
+        let mut dvs = Vec::with_capacity(servers.len());
+        let mut heads = Vec::with_capacity(servers.len());
+        for (i, server) in servers.iter().enumerate() {
+            let dv = server.update(new_epoch, d, &y_shares[i]);
+            if dv.epoch == new_epoch {
+                shares.push(y_shares[i].clone());
+                values.push(y_values[i]);
+                dvs.push(dv);
+            }
+            if let Some(head) = server.get_epoch_head(new_epoch) {
+                heads.push(head);
+            }
+        }
+        if dvs.len() < threshold {
+            return Err("not enough servers at epoch");
+        }
+        let accumulator =
+            SignedEpochHead::threshold_accumulator(&heads, &self.epoch_keys, new_epoch, threshold)?;
 
         // Post-processes the update and returns the witness
-        match self.post_update(
-            self.witness.as_ref().expect("to have a witness").witness,
-            threshold,
-            &y_shares,
-            &y_values,
-            &dvs,
-        ) {
-            Ok(new_witness) => {
-                if let Some(witness) = self.witness.as_mut() {
-                    witness.witness = new_witness;
-                }
+        let mut new_witness = witness.clone();
+        new_witness.witness =
+            self.post_update(new_witness.witness, threshold, &shares, &values, &dvs)?;
 
-                self.accumulator = servers[0].get_accumulator();
-                Ok(())
-            }
-            Err(e) => Err(e),
+        // Only commit to a witness that is valid for the signed accumulator
+        Witness::verify(
+            &accumulator,
+            &self.public_keys,
+            params,
+            &self.id,
+            &new_witness,
+        )
+        .map_err(|_| "invalid update")?;
+        Ok((new_witness, accumulator))
+    }
+
+    /// Updates to the latest available epoch from the aggregated range updates
+    /// of a single server. Unlike `update`, this reveals the user's epoch
+    /// but not their ID, and uses O(log) packets.
+    /// The updated witness is checked against the signed accumulator
+    pub fn range_update(
+        &mut self,
+        params: &AccParams,
+        server: &Server,
+    ) -> Result<(), &'static str> {
+        let mut witness = self.witness.clone().ok_or("No witness")?;
+        let head = server.get_head();
+        let accumulator = pinned_accumulator(&head, &self.epoch_keys)?;
+        let updates = server
+            .range_update(self.epoch, head.head.epoch)
+            .ok_or("invalid epoch")?;
+        witness.witness = RangeUpdate::apply(self.id, witness.witness, &updates)?;
+        Witness::verify(&accumulator, &self.public_keys, params, &self.id, &witness)
+            .map_err(|_| "invalid update")?;
+        self.witness = Some(witness);
+        self.epoch = head.head.epoch;
+        self.accumulator = accumulator;
+        Ok(())
+    }
+
+    /// Updates the non-membership witness to the latest signed head
+    /// from the server's public deletion receipts.
+    /// Fails if the user was deleted since the witness's epoch
+    pub fn non_membership_update(
+        &mut self,
+        params: &AccParams,
+        server: &Server,
+    ) -> Result<(), &'static str> {
+        let mut witness = self.non_membership.clone().ok_or("No witness")?;
+        let head = server.get_head();
+        let accumulator = pinned_accumulator(&head, &self.epoch_keys)?;
+        let receipts: Vec<DeletionReceipt> = (witness.epoch + 1..=head.head.epoch)
+            .map(|epoch| server.deletion_receipt(epoch).ok_or("invalid epoch"))
+            .collect::<Result<_, _>>()?;
+        witness.update(&self.id, &receipts)?;
+        if witness.epoch != head.head.epoch || witness.accumulator != accumulator {
+            return Err("invalid update");
         }
+        witness
+            .verify(&self.public_keys, params, &self.id)
+            .map_err(|_| "invalid update")?;
+        self.non_membership = Some(witness);
+        Ok(())
+    }
+
+    /// Constructs a proof that the user is not deleted
+    /// as of the epoch of their non-membership witness
+    pub fn make_non_membership_proof(
+        &self,
+        params: &AccParams,
+        public_keys: &PublicKeys,
+        ephemeral_challenge: &[u8; 2 * SECURITY_BYTES],
+    ) -> Option<NonMembershipProof> {
+        self.non_membership.as_ref().and_then(|witness| {
+            witness.make_non_membership_proof(&self.id, params, public_keys, ephemeral_challenge)
+        })
     }
 
     /// Constructs a membership proof as a byte string
@@ -303,14 +710,245 @@ impl User {
             Some(witness) => Witness::make_membership_proof(
                 witness,
                 &self.id,
-                &self.accumulator,
+                &self.statement(public_keys),
+                params,
+                ephemeral_challenge,
+            ),
+        }
+    }
+
+    /// Constructs a membership proof that also outputs the user's pseudonym for `scope`
+    pub fn make_scoped_membership_proof(
+        &self,
+        params: &AccParams,
+        public_keys: &PublicKeys,
+        scope: &[u8],
+        ephemeral_challenge: &[u8; 2 * SECURITY_BYTES],
+    ) -> Option<ScopedMembershipProof> {
+        match &self.witness {
+            None => None,
+            Some(witness) => Witness::make_scoped_membership_proof(
+                witness,
+                &self.id,
+                &self.statement(public_keys),
+                params,
+                scope,
+                ephemeral_challenge,
+            ),
+        }
+    }
+
+    /// Constructs a membership proof that signs `message`
+    pub fn make_presentation(
+        &self,
+        params: &AccParams,
+        public_keys: &PublicKeys,
+        message: &PresentationMessage,
+    ) -> Option<MembershipProof> {
+        match &self.witness {
+            None => None,
+            Some(witness) => Witness::make_presentation(
+                witness,
+                &self.id,
+                &self.statement(public_keys),
+                params,
+                message,
+            ),
+        }
+    }
+
+    /// Constructs the `counter`-th of the k presentations allowed in `limit`'s period.
+    /// Returns None if the counter is not below k
+    pub fn make_k_times_presentation(
+        &self,
+        params: &AccParams,
+        public_keys: &PublicKeys,
+        limit: &UsageLimit,
+        counter: usize,
+        ephemeral_challenge: &[u8; 2 * SECURITY_BYTES],
+    ) -> Option<KTimesPresentation> {
+        match &self.witness {
+            None => None,
+            Some(witness) => Witness::make_k_times_presentation(
+                witness,
+                &self.id,
+                &self.statement(public_keys),
+                params,
+                limit,
+                counter,
+                ephemeral_challenge,
+            ),
+        }
+    }
+
+    /// Constructs a membership proof whose user ID only `auditor_key`'s auditor can recover
+    pub fn make_traceable_presentation(
+        &self,
+        params: &AccParams,
+        public_keys: &PublicKeys,
+        auditor_key: &AuditorPublicKey,
+        ephemeral_challenge: &[u8; 2 * SECURITY_BYTES],
+    ) -> Option<TraceablePresentation> {
+        match &self.witness {
+            None => None,
+            Some(witness) => Witness::make_traceable_presentation(
+                witness,
+                &self.id,
+                &self.statement(public_keys),
+                params,
+                auditor_key,
+                ephemeral_challenge,
+            ),
+        }
+    }
+
+    /// Constructs a membership proof that only convinces `verifier_key`'s verifier
+    pub fn make_designated_membership_proof(
+        &self,
+        params: &AccParams,
+        public_keys: &PublicKeys,
+        verifier_key: &DesignatedVerifierPublicKey,
+        ephemeral_challenge: &[u8; 2 * SECURITY_BYTES],
+    ) -> Option<DesignatedProof> {
+        match &self.witness {
+            None => None,
+            Some(witness) => Witness::make_designated_membership_proof(
+                witness,
+                &self.id,
+                &self.statement(public_keys),
+                params,
+                verifier_key,
+                ephemeral_challenge,
+            ),
+        }
+    }
+
+    /// Constructs a membership proof for the user's credential
+    /// that reveals the attributes at the `disclose` indices
+    pub fn make_attribute_presentation(
+        &self,
+        params: &AccParams,
+        public_keys: &PublicKeys,
+        disclose: &[usize],
+        ephemeral_challenge: &[u8; 2 * SECURITY_BYTES],
+    ) -> Option<AttributePresentation> {
+        match (&self.witness, &self.credential) {
+            (Some(witness), Some(credential)) => Witness::make_attribute_presentation(
+                witness,
+                &self.id,
+                &self.statement(public_keys),
+                params,
+                credential,
+                disclose,
+                ephemeral_challenge,
+            ),
+            _ => None,
+        }
+    }
+
+    /// Constructs a presentation of the user's credential proving that
+    /// it expires later than the user's epoch, hiding all attributes
+    pub fn make_expiry_presentation(
+        &self,
+        params: &AccParams,
+        public_keys: &PublicKeys,
+        ephemeral_challenge: &[u8; 2 * SECURITY_BYTES],
+    ) -> Option<ExpiryPresentation> {
+        match (&self.witness, &self.credential) {
+            (Some(witness), Some(credential)) => Witness::make_expiry_presentation(
+                witness,
+                &self.id,
+                &self.statement(public_keys),
+                params,
+                credential,
+                ephemeral_challenge,
+            ),
+            _ => None,
+        }
+    }
+
+    /// Constructs one membership proof for the same ID under several issuers,
+    /// one user per issuer. Returns None if the users' IDs differ
+    /// or one of them has no witness
+    pub fn make_joint_membership_proof(
+        users: &[&User],
+        params: &AccParams,
+        ephemeral_challenge: &[u8; 2 * SECURITY_BYTES],
+    ) -> Option<JointMembershipProof> {
+        let id = users.first()?.id;
+        let issuers = users
+            .iter()
+            .map(|user| {
+                if user.id != id {
+                    return None;
+                }
+                Some(IssuerWitness {
+                    witness: user.witness.as_ref()?,
+                    statement: user.statement(&user.public_keys),
+                })
+            })
+            .collect::<Option<Vec<_>>>()?;
+        Witness::make_joint_membership_proof(&issuers, &id, params, ephemeral_challenge)
+    }
+
+    /// Constructs a membership proof that also outputs a Pedersen commitment
+    /// to the user's ID, along with the commitment's blinding
+    pub fn make_pedersen_membership_proof(
+        &self,
+        params: &AccParams,
+        public_keys: &PublicKeys,
+        generators: &PedersenGenerators,
+        ephemeral_challenge: &[u8; 2 * SECURITY_BYTES],
+    ) -> Option<(PedersenMembershipProof, Scalar)> {
+        match &self.witness {
+            None => None,
+            Some(witness) => Witness::make_pedersen_membership_proof(
+                witness,
+                &self.id,
+                &self.statement(public_keys),
+                params,
+                generators,
+                ephemeral_challenge,
+            ),
+        }
+    }
+
+    /// Constructs a membership proof that can be checked in a `MembershipProofBatch`
+    pub fn make_committed_membership_proof(
+        &self,
+        params: &AccParams,
+        public_keys: &PublicKeys,
+        ephemeral_challenge: &[u8; 2 * SECURITY_BYTES],
+    ) -> Option<CommittedMembershipProof> {
+        match &self.witness {
+            None => None,
+            Some(witness) => Witness::make_committed_membership_proof(
+                witness,
+                &self.id,
+                &self.statement(public_keys),
                 params,
-                public_keys,
                 ephemeral_challenge,
             ),
         }
     }
 
+    /// Checks that the user's accumulator is the one a threshold of
+    /// independent servers signed for the user's epoch, counting only heads
+    /// under distinct pinned keys. A server that hands out a unique
+    /// accumulator to tag a user fails this check
+    pub fn check_accumulator(
+        &self,
+        heads: &[SignedEpochHead],
+        threshold: usize,
+    ) -> Result<(), &'static str> {
+        let accumulator =
+            SignedEpochHead::threshold_accumulator(heads, &self.epoch_keys, self.epoch, threshold)?;
+        if accumulator != self.accumulator {
+            return Err("accumulator differs from the servers");
+        }
+        Ok(())
+    }
+
     /// Checks whether the user has a valid witness for the given accumulator
     /// Compares to the user's internal copy of the signature key
     pub fn check_witness(
@@ -327,6 +965,33 @@ impl User {
     }
 }
 
+// The accumulator of `head` if it is signed under one of the pinned keys
+fn pinned_accumulator(
+    head: &SignedEpochHead,
+    epoch_keys: &[EpochPublicKey],
+) -> Result<Accumulator, &'static str> {
+    epoch_keys
+        .iter()
+        .find_map(|key| head.accumulator(key).ok())
+        .ok_or("head not signed under a pinned key")
+}
+
+// The last bucket boundary at or before `epoch`,
+// where epochs 1, 1 + bucket, 1 + 2*bucket, ... are boundaries
+fn bucket_boundary(epoch: usize, bucket: usize) -> usize {
+    epoch - (epoch - 1) % bucket
+}
+
+/// Returns the highest epoch that at least `threshold` of the given epochs have reached
+pub(crate) fn threshold_epoch(epochs: &[usize], threshold: usize) -> Result<usize, &'static str> {
+    if threshold == 0 || epochs.len() < threshold {
+        return Err("invalid threshold");
+    }
+    let mut epochs = epochs.to_vec();
+    epochs.sort_unstable_by(|a, b| b.cmp(a));
+    Ok(epochs[threshold - 1])
+}
+
 /// A user update message
 #[derive(Clone, Debug)]
 pub struct UserUpdate {
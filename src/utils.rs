@@ -94,6 +94,36 @@ impl AccParams {
     pub fn get_z1(&self) -> G1Projective {
         self.z1
     }
+    /// Get the generator H_i of the i-th signed attribute.
+    /// These are derived on demand, so a signature can cover any number of attributes
+    pub fn get_h1(&self, i: usize) -> G1Projective {
+        const DST_G1: &[u8] = b"BLS12381G1_XMD:SHA-256_SSWU_RO_";
+        let mut array = [0xFFu8; 32];
+        array[0] = 0xF9;
+        array[24..].copy_from_slice(&(i as u64).to_be_bytes());
+        G1Projective::hash::<ExpandMsgXmd<sha2::Sha256>>(&array, DST_G1)
+    }
+    /// Get the generator H_0 of the blinding s of a credential signature
+    pub fn get_h0(&self) -> G1Projective {
+        const DST_G1: &[u8] = b"BLS12381G1_XMD:SHA-256_SSWU_RO_";
+        let mut array = [0xFFu8; 32];
+        array[0] = 0xF8;
+        G1Projective::hash::<ExpandMsgXmd<sha2::Sha256>>(&array, DST_G1)
+    }
+    /// Get the generator H_y of the user ID in a credential signature
+    pub fn get_hy(&self) -> G1Projective {
+        const DST_G1: &[u8] = b"BLS12381G1_XMD:SHA-256_SSWU_RO_";
+        let mut array = [0xFFu8; 32];
+        array[0] = 0xF7;
+        G1Projective::hash::<ExpandMsgXmd<sha2::Sha256>>(&array, DST_G1)
+    }
+    /// Get the generator H_e of the expiry epoch in a credential signature
+    pub fn get_he(&self) -> G1Projective {
+        const DST_G1: &[u8] = b"BLS12381G1_XMD:SHA-256_SSWU_RO_";
+        let mut array = [0xFFu8; 32];
+        array[0] = 0xF6;
+        G1Projective::hash::<ExpandMsgXmd<sha2::Sha256>>(&array, DST_G1)
+    }
 
     /// Add these proof params to the transcript
     pub fn add_to_transcript(&self, transcript: &mut Transcript) {
@@ -180,19 +210,16 @@ pub(crate) fn shamir_rebuild_scalar(
     for i in 0..coefficients.len() {
         result += shares[i].1 * coefficients[i];
     }
-    match check_coefficients {
-        Some(checks) => {
-            let threshold = coefficients.len();
-            let mut check_result = checks[0] * shares[threshold].1;
-            for i in 1..threshold {
-                check_result += checks[i] * shares[i].1;
-            }
-            if check_result == result {
-                return Some(result);
-            }
-            return None;
+    if let Some(checks) = check_coefficients {
+        let threshold = coefficients.len();
+        let mut check_result = checks[0] * shares[threshold].1;
+        for i in 1..threshold {
+            check_result += checks[i] * shares[i].1;
+        }
+        if check_result == result {
+            return Some(result);
         }
-        None => {}
+        return None;
     }
     Some(result)
 }
@@ -209,19 +236,16 @@ pub(crate) fn shamir_rebuild_point(
     for i in 0..coefficients.len() {
         result += shares[i].1 * coefficients[i];
     }
-    match check_coefficients {
-        Some(checks) => {
-            let threshold = coefficients.len();
-            let mut check_result = shares[threshold].1 * checks[0];
-            for i in 1..threshold {
-                check_result += shares[i].1 * checks[i];
-            }
-            if check_result == result {
-                return Some(result);
-            }
-            return None;
+    if let Some(checks) = check_coefficients {
+        let threshold = coefficients.len();
+        let mut check_result = shares[threshold].1 * checks[0];
+        for i in 1..threshold {
+            check_result += shares[i].1 * checks[i];
+        }
+        if check_result == result {
+            return Some(result);
         }
-        None => {}
+        return None;
     }
     Some(result)
 }
@@ -236,3 +260,30 @@ pub(crate) fn sc(b: &[u8]) -> Result<Scalar, &'static str> {
     let buf = <[u8; 32]>::try_from(b).map_err(|_| "Proof serialization error")?;
     Option::<Scalar>::from(Scalar::from_be_bytes(&buf)).ok_or("Proof serialization error")
 }
+
+pub(crate) fn usize_from(b: &[u8]) -> Result<usize, &'static str> {
+    let buf = <[u8; 8]>::try_from(b).map_err(|_| "invalid length")?;
+    usize::try_from(u64::from_be_bytes(buf)).map_err(|_| "invalid length")
+}
+
+/// Returns the indices of the items that fail `check`, given that `check`
+/// passes a slice only if every item in it passes.
+/// Slices that fail are halved until single failing items remain
+pub(crate) fn bisect_failures<T>(items: &[T], check: &impl Fn(&[T]) -> bool) -> Vec<usize> {
+    let mut failing = Vec::new();
+    bisect(items, 0, check, &mut failing);
+    failing
+}
+
+fn bisect<T>(items: &[T], offset: usize, check: &impl Fn(&[T]) -> bool, failing: &mut Vec<usize>) {
+    if items.is_empty() || check(items) {
+        return;
+    }
+    if items.len() == 1 {
+        failing.push(offset);
+        return;
+    }
+    let mid = items.len() / 2;
+    bisect(&items[..mid], offset, check, failing);
+    bisect(&items[mid..], offset + mid, check, failing);
+}
@@ -0,0 +1,171 @@
+use crate::epoch::{EpochPublicKey, SignedEpochHead};
+use crate::utils::{AccParams, PublicKeys, SECURITY_BYTES};
+use crate::witness::{MembershipProof, Witness};
+use rand::RngCore;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// A challenge a verifier issues for a single membership proof
+pub type Challenge = [u8; 2 * SECURITY_BYTES];
+
+/// The outcome of checking a membership proof
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum VerificationResult {
+    /// The proof is valid for the accumulator of this epoch
+    Accepted {
+        /// The epoch of the accumulator the proof was checked against
+        epoch: usize,
+    },
+    /// The challenge was not issued by this verifier
+    UnknownChallenge,
+    /// The challenge expired before the proof arrived
+    ExpiredChallenge,
+    /// The challenge was already used for another proof
+    ReplayedChallenge,
+    /// The verifier has not accepted a signed head yet
+    NoAcceptedHead,
+    /// The proof is for an epoch outside the window of accepted epochs,
+    /// or one whose head the verifier has not accepted
+    EpochNotAccepted {
+        /// The epoch the proof was made against
+        epoch: usize,
+    },
+    /// The proof does not verify
+    InvalidProof,
+}
+
+impl VerificationResult {
+    /// Whether the proof was accepted
+    pub fn is_accepted(&self) -> bool {
+        matches!(self, Self::Accepted { .. })
+    }
+}
+
+/// A relying party that checks membership proofs.
+/// It issues single-use challenges that expire, and only checks proofs
+/// against accumulators that come with a validly signed epoch head.
+/// Proofs are accepted for any of the last `epoch_window` epochs,
+/// so users who have not updated recently can still prove membership
+#[derive(Clone, Debug)]
+pub struct Verifier {
+    params: AccParams,
+    public_keys: PublicKeys,
+    epoch_public_key: EpochPublicKey,
+    challenge_lifetime: Duration,
+    epoch_window: usize,
+    heads: Vec<SignedEpochHead>,
+    pending: HashMap<Challenge, Instant>,
+    used: HashMap<Challenge, Instant>,
+}
+
+impl Verifier {
+    /// Creates a verifier that trusts heads signed under `epoch_public_key`,
+    /// which may be a single server's key or the servers' joint key
+    pub fn new(
+        params: AccParams,
+        public_keys: PublicKeys,
+        epoch_public_key: EpochPublicKey,
+        challenge_lifetime: Duration,
+    ) -> Self {
+        Self {
+            params,
+            public_keys,
+            epoch_public_key,
+            challenge_lifetime,
+            epoch_window: 1,
+            heads: Vec::new(),
+            pending: HashMap::new(),
+            used: HashMap::new(),
+        }
+    }
+
+    /// Accepts proofs for any of the last `epoch_window` epochs, which must be at least 1.
+    /// The default only accepts the latest epoch
+    pub fn set_epoch_window(&mut self, epoch_window: usize) -> Result<(), &'static str> {
+        if epoch_window == 0 {
+            return Err("invalid epoch window");
+        }
+        self.epoch_window = epoch_window;
+        Ok(())
+    }
+
+    /// Accepts the accumulator of a signed head for an epoch not accepted yet.
+    /// Heads older than the latest one are accepted if they are inside the epoch window
+    pub fn accept_head(&mut self, head: SignedEpochHead) -> Result<(), &'static str> {
+        head.verify(&self.epoch_public_key)?;
+        let epoch = head.head.epoch;
+        if let Some(latest) = self.heads.last().map(|h| h.head.epoch) {
+            if epoch + self.epoch_window <= latest {
+                return Err("head is outside the epoch window");
+            }
+        }
+        match self.heads.binary_search_by_key(&epoch, |h| h.head.epoch) {
+            Ok(_) => Err("head already accepted"),
+            Err(index) => {
+                self.heads.insert(index, head);
+                Ok(())
+            }
+        }
+    }
+
+    /// The latest accepted head
+    pub fn get_head(&self) -> Option<SignedEpochHead> {
+        self.heads.last().copied()
+    }
+
+    /// Issues a fresh challenge for one membership proof
+    pub fn issue_challenge(&mut self) -> Challenge {
+        let now = Instant::now();
+        self.prune(now);
+        let mut challenge = [0u8; 2 * SECURITY_BYTES];
+        rand::rngs::OsRng.fill_bytes(&mut challenge);
+        self.pending
+            .insert(challenge, now + self.challenge_lifetime);
+        challenge
+    }
+
+    /// Checks a membership proof made for `challenge`.
+    /// The challenge is used up whether or not the proof is valid
+    pub fn verify(&mut self, proof: &MembershipProof, challenge: &Challenge) -> VerificationResult {
+        let now = Instant::now();
+        if self.used.contains_key(challenge) {
+            return VerificationResult::ReplayedChallenge;
+        }
+        let expiry = match self.pending.remove(challenge) {
+            Some(expiry) => expiry,
+            None => return VerificationResult::UnknownChallenge,
+        };
+        self.used.insert(*challenge, expiry);
+        self.prune(now);
+        if now >= expiry {
+            return VerificationResult::ExpiredChallenge;
+        }
+        let latest = match self.heads.last() {
+            Some(head) => head.head.epoch,
+            None => return VerificationResult::NoAcceptedHead,
+        };
+        let epoch = proof.epoch();
+        let head = match self.heads.iter().find(|h| h.head.epoch == epoch) {
+            Some(head) if epoch + self.epoch_window > latest => head.head,
+            _ => return VerificationResult::EpochNotAccepted { epoch },
+        };
+        if Witness::check_membership_proof(
+            proof,
+            &self.params,
+            &self.public_keys,
+            &head.accumulator,
+            challenge,
+        ) {
+            VerificationResult::Accepted { epoch }
+        } else {
+            VerificationResult::InvalidProof
+        }
+    }
+
+    // Forgets challenges once they expire; a used challenge
+    // that is replayed after that is rejected as unknown
+    fn prune(&mut self, now: Instant) {
+        self.pending.retain(|_, expiry| *expiry > now);
+        self.used.retain(|_, expiry| *expiry > now);
+    }
+}
@@ -1,7 +1,9 @@
 use crate::accumulator::{
-    generate_fr, pair, schnorr, Accumulator, Element, MembershipWitness, SecretKey, SALT,
+    generate_fr, pair, schnorr, Accumulator, Element, MembershipWitness, ProofMessage, SecretKey,
+    SALT,
 };
-use crate::utils::{g1, sc};
+use crate::epoch::{EpochPublicKey, SignedEpochHead};
+use crate::utils::{g1, sc, usize_from};
 use blsful::inner_types::*;
 use merlin::Transcript;
 use serde::{Deserialize, Serialize};
@@ -85,42 +87,105 @@ impl Witness {
     /// Constructs a membership proof as a byte string
     /// Most of the work happens in creating mpc
     /// and  mpc.gen_proof
+    /// To also output a commitment to y, use `make_pedersen_membership_proof`
     pub fn make_membership_proof(
         witness: &Witness,
         user_id: &UserID,
-        accumulator: &Accumulator,
+        statement: &Statement,
         params: &AccParams,
-        public_keys: &PublicKeys,
         ephemeral_challenge: &[u8; 2 * SECURITY_BYTES],
     ) -> Option<MembershipProof> {
+        Self::prove_bound(witness, user_id, statement, params, |transcript, _| {
+            transcript.append_message(b"Ephemeral challenge", ephemeral_challenge)
+        })
+    }
+
+    // Makes a proof whose challenge also hashes whatever `bind` adds to the transcript.
+    // `bind` also gets the blindings k_0,..k_7, so that it can commit to more
+    // statements about the same secret key (k_0, answered by s_0) or user ID (k_7, by s_7)
+    pub(crate) fn prove_bound(
+        witness: &Witness,
+        user_id: &UserID,
+        statement: &Statement,
+        params: &AccParams,
+        bind: impl FnOnce(&mut Transcript, &[Scalar; 8]),
+    ) -> Option<MembershipProof> {
+        Self::prove_split(witness, user_id, statement, params, |transcript, k| {
+            bind(transcript, k);
+            Scalar::ZERO
+        })
+    }
+
+    // Like `prove_bound`, but as one branch of an OR proof:
+    // `bind` returns the share of the challenge taken by the other branch,
+    // and the proof answers the rest of the challenge
+    pub(crate) fn prove_split(
+        witness: &Witness,
+        user_id: &UserID,
+        statement: &Statement,
+        params: &AccParams,
+        bind: impl FnOnce(&mut Transcript, &[Scalar; 8]) -> Scalar,
+    ) -> Option<MembershipProof> {
+        Self::prove(witness, user_id, statement, params, bind).map(|(_, proof)| proof)
+    }
+
+    /// Constructs a membership proof that also carries its commitments,
+    /// so that many proofs can be checked at once with `MembershipProofBatch`
+    pub fn make_committed_membership_proof(
+        witness: &Witness,
+        user_id: &UserID,
+        statement: &Statement,
+        params: &AccParams,
+        ephemeral_challenge: &[u8; 2 * SECURITY_BYTES],
+    ) -> Option<CommittedMembershipProof> {
+        Self::prove(witness, user_id, statement, params, |transcript, _| {
+            transcript.append_message(b"Ephemeral challenge", ephemeral_challenge);
+            Scalar::ZERO
+        })
+        .map(|(mpc, proof)| CommittedMembershipProof {
+            proof,
+            t_1: mpc.t_1,
+            t_2: mpc.t_2,
+            pi_1: mpc.pi_1,
+            pi_2: mpc.pi_2,
+        })
+    }
+
+    fn prove(
+        witness: &Witness,
+        user_id: &UserID,
+        statement: &Statement,
+        params: &AccParams,
+        bind: impl FnOnce(&mut Transcript, &[Scalar; 8]) -> Scalar,
+    ) -> Option<(MembershipProofCommitting, MembershipProof)> {
+        let Statement {
+            public_keys,
+            accumulator,
+            epoch,
+        } = *statement;
         // Check that witness is valid
         Self::verify(accumulator, public_keys, params, user_id, witness).ok()?;
 
         // Create commitment
-        let mpc = MembershipProofCommitting::new(witness, params, public_keys);
+        let mpc = MembershipProofCommitting::new(
+            witness,
+            ProofMessage::Hidden { message: user_id.0 },
+            params,
+            public_keys,
+        );
 
         // Commit to public parameters
-        let mut transcript = Transcript::new(b"basic_membership_proof");
-        transcript.append_message(
-            b"Signature Public Key",
-            public_keys.witness_key.to_bytes().as_ref(),
-        );
-        transcript.append_message(
-            b"Witness Public Key",
-            public_keys.sign_key.to_bytes().as_ref(),
-        );
-        transcript.append_message(b"Accumulator", accumulator.to_bytes().as_ref());
-        params.add_to_transcript(&mut transcript);
+        let mut transcript = membership_transcript(params, public_keys, accumulator, epoch);
         // Add the elements of the proof to the commitment
         mpc.get_bytes_for_challenge(&mut transcript);
         // Add the challenge
-        transcript.append_message(b"Ephemeral challenge", ephemeral_challenge);
+        let share = bind(&mut transcript, &mpc.k);
         // Create challenge hash
         let challenge = Element::from_transcript(b"challenge", &mut transcript);
         // Construct response and remove unnecessary elements of the commitment
-        let proof = mpc.gen_proof(witness, user_id, challenge);
+        let proof = mpc.gen_proof(witness, user_id, epoch, Element(challenge.0 - share));
 
-        Some(proof)
+        Some((mpc, proof))
     }
 
     /// Verifies a ZKPoK membership proof given as byte string
@@ -131,33 +196,104 @@ impl Witness {
         accumulator: &Accumulator,
         ephemeral_challenge: &[u8; 2 * SECURITY_BYTES],
     ) -> bool {
+        Self::check_bound(proof, params, public_keys, accumulator, |transcript| {
+            transcript.append_message(b"Ephemeral challenge", ephemeral_challenge)
+        })
+    }
+
+    // Checks a proof made by `prove_bound` with the same `bind`
+    pub(crate) fn check_bound(
+        proof: &MembershipProof,
+        params: &AccParams,
+        public_keys: &PublicKeys,
+        accumulator: &Accumulator,
+        bind: impl FnOnce(&mut Transcript),
+    ) -> bool {
+        Self::bound_challenge(proof, params, public_keys, accumulator, bind) == proof.challenge
+    }
+
+    // Recomputes the challenge hash of a proof made by `prove_bound` or `prove_split`
+    // with the same `bind`. For `prove_split`, the proof's own challenge
+    // is this hash minus the other branch's share
+    pub(crate) fn bound_challenge(
+        proof: &MembershipProof,
+        params: &AccParams,
+        public_keys: &PublicKeys,
+        accumulator: &Accumulator,
+        bind: impl FnOnce(&mut Transcript),
+    ) -> Scalar {
         // Construct commitments to public parameters/keys
-        let mut transcript = Transcript::new(b"basic_membership_proof");
-        transcript.append_message(
-            b"Signature Public Key",
-            public_keys.witness_key.to_bytes().as_ref(),
-        );
-        transcript.append_message(
-            b"Witness Public Key",
-            public_keys.sign_key.to_bytes().as_ref(),
-        );
-        transcript.append_message(b"Accumulator", accumulator.to_bytes().as_ref());
-        params.add_to_transcript(&mut transcript);
+        let mut transcript = membership_transcript(params, public_keys, accumulator, proof.epoch);
 
         // Reconstruct all necessary points and add them to the transcript
         proof.get_bytes_for_challenge(params, public_keys, accumulator, &mut transcript);
-        transcript.append_message(b"Ephemeral challenge", ephemeral_challenge);
-        // Verifies that the full reconstructed transcript matches the hash
-        let challenge = Element::from_transcript(b"challenge", &mut transcript);
-        challenge.0 == proof.challenge
+        bind(&mut transcript);
+        Element::from_transcript(b"challenge", &mut transcript).0
     }
+
+    /// Verifies a ZKPoK membership proof against the accumulator of a signed head,
+    /// rejecting the proof if the head is not signed by the server's epoch key
+    /// or is for a different epoch than the proof
+    pub fn check_membership_proof_at_head(
+        proof: &MembershipProof,
+        params: &AccParams,
+        public_keys: &PublicKeys,
+        head: &SignedEpochHead,
+        epoch_public_key: &EpochPublicKey,
+        ephemeral_challenge: &[u8; 2 * SECURITY_BYTES],
+    ) -> bool {
+        match head.accumulator(epoch_public_key) {
+            Ok(accumulator) if head.head.epoch == proof.epoch => Self::check_membership_proof(
+                proof,
+                params,
+                public_keys,
+                &accumulator,
+                ephemeral_challenge,
+            ),
+            _ => false,
+        }
+    }
+}
+
+/// What a membership proof is made against:
+/// the issuer's public keys and its accumulator at an epoch
+#[derive(Copy, Clone, Debug)]
+pub struct Statement<'a> {
+    /// The issuer's public keys
+    pub public_keys: &'a PublicKeys,
+    /// The accumulator the witness is valid for
+    pub accumulator: &'a Accumulator,
+    /// The epoch of that accumulator
+    pub epoch: usize,
+}
+
+// Starts a membership proof transcript with the public parameters and keys
+pub(crate) fn membership_transcript(
+    params: &AccParams,
+    public_keys: &PublicKeys,
+    accumulator: &Accumulator,
+    epoch: usize,
+) -> Transcript {
+    let mut transcript = Transcript::new(b"basic_membership_proof");
+    transcript.append_message(
+        b"Signature Public Key",
+        public_keys.witness_key.to_bytes().as_ref(),
+    );
+    transcript.append_message(
+        b"Witness Public Key",
+        public_keys.sign_key.to_bytes().as_ref(),
+    );
+    transcript.append_message(b"Accumulator", accumulator.to_bytes().as_ref());
+    transcript.append_u64(b"Epoch", epoch as u64);
+    params.add_to_transcript(&mut transcript);
+    transcript
 }
 
 /// The commit or blinding step for generating a ZKP
 /// The next step is to call `get_bytes_for_challenge`
 /// to create the fiat shamir heuristic
 #[derive(Debug, Copy, Clone)]
-struct MembershipProofCommitting {
+pub(crate) struct MembershipProofCommitting {
     pub r: [Scalar; 3],
     pub k: [Scalar; 8],
     pub u_1: G1Projective,
@@ -172,9 +308,15 @@ struct MembershipProofCommitting {
 impl MembershipProofCommitting {
     /// Create a new membership proof committing phase
     // Follows the ZKPoK in the PROVE function on page 88
-    pub fn new(witness: &Witness, params: &AccParams, public_keys: &PublicKeys) -> Self {
+    // k_7 is the blinding of y, which is shared when y is a `ProofMessage::SharedBlinding`
+    pub fn new(
+        witness: &Witness,
+        y: ProofMessage,
+        params: &AccParams,
+        public_keys: &PublicKeys,
+    ) -> Self {
         let rng = rand::rngs::OsRng;
-        // Randomly select r_1, r_2, r_3, k_0,..k_7
+        // Randomly select r_1, r_2, r_3, k_0,..k_6
         let r: [Scalar; 3] = [
             generate_fr(SALT, None, rng),
             generate_fr(SALT, None, rng),
@@ -188,7 +330,7 @@ impl MembershipProofCommitting {
             generate_fr(SALT, None, rng),
             generate_fr(SALT, None, rng),
             generate_fr(SALT, None, rng),
-            generate_fr(SALT, None, rng),
+            y.get_blinder(rng),
         ];
 
         // U_1 = R_m + r_1Y
@@ -254,6 +396,7 @@ impl MembershipProofCommitting {
         &self,
         witness: &Witness,
         user_id: &UserID,
+        epoch: usize,
         challenge_hash: Element,
     ) -> MembershipProof {
         let challenge_hash = challenge_hash.0;
@@ -268,6 +411,7 @@ impl MembershipProofCommitting {
         let s7 = schnorr(self.k[7], user_id.0, challenge_hash);
 
         MembershipProof {
+            epoch,
             u_1: self.u_1,
             u_2: self.u_2,
             r: self.r_point,
@@ -291,6 +435,7 @@ impl MembershipProofCommitting {
 // to use a proof to reconstruct missing parts of it
 #[derive(Debug, Default, Copy, Clone, Deserialize, Serialize)]
 pub struct MembershipProof {
+    pub(crate) epoch: usize,
     pub(crate) u_1: G1Projective,
     pub(crate) u_2: G1Projective,
     pub(crate) r: G1Projective,
@@ -309,8 +454,9 @@ impl Display for MembershipProof {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         write!(
             f,
-            "MembershipProof {{ u_1: {}, u_2: {}, r: {}, challenge {}, s_0: {}
+            "MembershipProof {{ epoch: {}, u_1: {}, u_2: {}, r: {}, challenge {}, s_0: {}
              s_1: {}, s_2: {},  s_3: {},  s_4: {},  s_5: {},  s_6: {},  s_7: {} }}",
+            self.epoch,
             self.u_1,
             self.u_2,
             self.r,
@@ -329,7 +475,12 @@ impl Display for MembershipProof {
 
 impl MembershipProof {
     /// The size of the proof in bytes
-    pub const BYTES: usize = 432;
+    pub const BYTES: usize = 440;
+
+    /// The epoch of the accumulator the proof was made against
+    pub fn epoch(&self) -> usize {
+        self.epoch
+    }
 
     /// Get the byte representation of the proof
     pub fn to_bytes(&self) -> [u8; Self::BYTES] {
@@ -346,12 +497,14 @@ impl MembershipProof {
         res[336..368].copy_from_slice(&self.s_5.to_be_bytes());
         res[368..400].copy_from_slice(&self.s_6.to_be_bytes());
         res[400..432].copy_from_slice(&self.s_7.to_be_bytes());
+        res[432..440].copy_from_slice(&(self.epoch as u64).to_be_bytes());
         res
     }
 
     /// Convert a byte representation to a proof
     pub fn from_bytes(input: &[u8; Self::BYTES]) -> Result<Self, &'static str> {
         Ok(Self {
+            epoch: usize_from(&input[432..440])?,
             u_1: g1(&input[0..48])?,
             u_2: g1(&input[48..96])?,
             r: g1(&input[96..144])?,
@@ -409,3 +562,51 @@ impl MembershipProof {
         transcript.append_message(b"Pi_2", pi_2.to_bytes().as_ref());
     }
 }
+
+/// A membership proof that also carries its commitments T_1, T_2, Pi_1, Pi_2.
+/// A verifier checks the challenge by hashing alone and can then batch
+/// the pairing checks of many proofs. The inner proof is a regular
+/// `MembershipProof` for the same accumulator and challenge
+#[derive(Debug, Copy, Clone, Deserialize, Serialize)]
+pub struct CommittedMembershipProof {
+    pub(crate) proof: MembershipProof,
+    pub(crate) t_1: G1Projective,
+    pub(crate) t_2: G1Projective,
+    pub(crate) pi_1: Gt,
+    pub(crate) pi_2: Gt,
+}
+
+impl CommittedMembershipProof {
+    /// The proof without its commitments
+    pub fn proof(&self) -> MembershipProof {
+        self.proof
+    }
+
+    /// The epoch of the accumulator the proof was made against
+    pub fn epoch(&self) -> usize {
+        self.proof.epoch
+    }
+
+    /// Whether the challenge is the hash of the carried commitments.
+    /// The commitments still need to be checked against the responses
+    pub(crate) fn check_challenge(
+        &self,
+        params: &AccParams,
+        public_keys: &PublicKeys,
+        accumulator: &Accumulator,
+        ephemeral_challenge: &[u8; 2 * SECURITY_BYTES],
+    ) -> bool {
+        let mut transcript =
+            membership_transcript(params, public_keys, accumulator, self.proof.epoch);
+        transcript.append_message(b"U_1", &self.proof.u_1.to_compressed());
+        transcript.append_message(b"U_2", &self.proof.u_2.to_compressed());
+        transcript.append_message(b"R", &self.proof.r.to_compressed());
+        transcript.append_message(b"T_1", &self.t_1.to_compressed());
+        transcript.append_message(b"T_2", &self.t_2.to_compressed());
+        transcript.append_message(b"Pi_1", self.pi_1.to_bytes().as_ref());
+        transcript.append_message(b"Pi_2", self.pi_2.to_bytes().as_ref());
+        transcript.append_message(b"Ephemeral challenge", ephemeral_challenge);
+        let challenge = Element::from_transcript(b"challenge", &mut transcript);
+        challenge.0 == self.proof.challenge
+    }
+}